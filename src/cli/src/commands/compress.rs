@@ -1,5 +1,5 @@
 use crate::macros::AbortableResult;
-use prs_rs::{comp::prs_compress_unsafe, util::prs_calculate_max_compressed_size};
+use prs_rs::comp::prs_compress;
 use rayon::prelude::*;
 use std::fs::{create_dir_all, read, remove_file, write};
 use std::path::Path;
@@ -78,19 +78,8 @@ fn compress_file_to_target(source_file: &Path, target: &Path) {
 
 fn compress_file(input_path: &Path, output_path: &Path) {
     let original_data = read(input_path).unwrap_abort();
-    let alloc_size = prs_calculate_max_compressed_size(original_data.len());
-    let mut compressed_data = Box::<[u8]>::new_uninit_slice(alloc_size);
-    let compressed_size = unsafe {
-        prs_compress_unsafe(
-            original_data.as_ptr(),
-            original_data.len(),
-            compressed_data.as_mut_ptr() as *mut u8,
-        )
-    };
-
-    let compressed_data = unsafe { compressed_data.assume_init() };
-    let compressed_slice = &compressed_data[0..compressed_size];
-    write(output_path, compressed_slice).unwrap_abort();
+    let compressed_data = prs_compress(&original_data);
+    write(output_path, compressed_data).unwrap_abort();
 }
 
 fn compress_file_inplace(input_path: &Path, output_path: &Path) {
@@ -1,5 +1,5 @@
 use crate::macros::AbortableResult;
-use prs_rs::decomp::{prs_calculate_decompressed_size, prs_decompress_unsafe};
+use prs_rs::decomp::prs_decompress_to_vec;
 use rayon::prelude::*;
 use std::fs::{create_dir_all, read, remove_file, write};
 use std::path::Path;
@@ -96,11 +96,9 @@ fn decompress_file_to_target(source_file: &Path, target: &Path) {
 
 fn decompress_file(input_path: &Path, output_path: &Path) {
     let compressed_data = read(input_path).unwrap_abort();
-    let estimate: usize = unsafe { prs_calculate_decompressed_size(compressed_data.as_ptr()) };
-    let mut decomp = Box::<[u8]>::new_uninit_slice(estimate);
-
-    unsafe { prs_decompress_unsafe(compressed_data.as_ptr(), decomp.as_mut_ptr() as *mut u8) };
-    let decomp = unsafe { decomp.assume_init() };
+    let decomp = prs_decompress_to_vec(&compressed_data).unwrap_or_else(|e| {
+        abort!("Fail: {}. {}", input_path.display(), e);
+    });
     write(output_path, decomp).unwrap_abort();
 }
 
@@ -1,8 +1,7 @@
 use crate::macros::AbortableResult;
 use prs_rs::{
-    comp::prs_compress_unsafe,
+    comp::prs_compress,
     decomp::{prs_calculate_decompressed_size, prs_decompress_unsafe},
-    util::prs_calculate_max_compressed_size,
 };
 use rayon::prelude::*;
 use std::fs::read;
@@ -51,17 +50,7 @@ fn test_file(file_path: &Path) {
         return;
     }
 
-    let mut comp =
-        Box::<[u8]>::new_uninit_slice(prs_calculate_max_compressed_size(original_data.len()));
-    unsafe {
-        prs_compress_unsafe(
-            original_data.as_ptr(),
-            original_data.len(),
-            comp.as_mut_ptr() as *mut u8,
-        )
-    };
-
-    let comp = unsafe { comp.assume_init() };
+    let comp = prs_compress(&original_data);
     let estimate: usize = unsafe { prs_calculate_decompressed_size(&*comp) };
 
     let mut decomp = Box::<[u8]>::new_uninit_slice(estimate);
@@ -0,0 +1,66 @@
+//! Compresses and decompresses data in a `no_std` binary without a global allocator.
+//! This fails to link if anything used by [`prs_compress_with_scratch`] requires `alloc`.
+//!
+//! Run with `cargo run` from this directory; exits with 0 on success.
+
+#![no_std]
+#![no_main]
+
+use core::mem::MaybeUninit;
+use prs_rs::comp::{
+    prs_compress_scratch_size, prs_compress_with_scratch, CompressionLevel, CompressionOptions,
+};
+use prs_rs::decomp::prs_decompress;
+use prs_rs::util::prs_calculate_max_compressed_size;
+
+/// Above the small input threshold, so the dictionary is used.
+const DATA_LEN: usize = 0x2000;
+const SCRATCH_LEN: usize = 0x200000;
+
+// libc provides the entry point, and `memcpy` etc.
+#[link(name = "c")]
+extern "C" {
+    fn abort() -> !;
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    unsafe { abort() }
+}
+
+// The precompiled `core` is built with unwinding, so refers to this; nothing unwinds with `panic = "abort"`.
+#[no_mangle]
+extern "C" fn rust_eh_personality() {}
+
+#[no_mangle]
+pub extern "C" fn main(_argc: i32, _argv: *const *const u8) -> i32 {
+    let mut data = [0_u8; DATA_LEN];
+    for (x, byte) in data.iter_mut().enumerate() {
+        *byte = ((x % 251) ^ (x / 97)) as u8;
+    }
+
+    let mut scratch = [0_u8; SCRATCH_LEN];
+    let mut compressed = [MaybeUninit::uninit(); DATA_LEN * 2];
+    assert!(prs_calculate_max_compressed_size(DATA_LEN) <= compressed.len());
+    let mut decompressed = [0_u8; DATA_LEN];
+    for level in [
+        CompressionLevel::Greedy,
+        CompressionLevel::Lazy,
+        CompressionLevel::Fast,
+        CompressionLevel::Optimal,
+        CompressionLevel::Sega,
+    ] {
+        let options = CompressionOptions::new(level);
+        assert!(prs_compress_scratch_size(DATA_LEN, &options) <= SCRATCH_LEN);
+
+        let compressed_len =
+            prs_compress_with_scratch(&data, &mut compressed, &options, &mut scratch).unwrap();
+        let compressed =
+            unsafe { &*(&compressed[..compressed_len] as *const [MaybeUninit<u8>] as *const [u8]) };
+
+        assert_eq!(prs_decompress(compressed, &mut decompressed), Ok(DATA_LEN));
+        assert!(decompressed == data);
+    }
+
+    0
+}
@@ -1,5 +1,7 @@
 use criterion::{BenchmarkId, Criterion, Throughput};
-use prs_rs::comp::prs_compress_unsafe;
+use prs_rs::comp::{
+    prs_compress_unsafe, prs_compress_with_options, CompressionLevel, CompressionOptions,
+};
 use prs_rs::util::prs_calculate_max_compressed_size;
 use std::hint::black_box;
 
@@ -31,6 +33,13 @@ pub fn bench_compress_file(c: &mut Criterion) {
                 })
             },
         );
+
+        let options = CompressionOptions::new(CompressionLevel::Fast);
+        group.bench_with_input(
+            BenchmarkId::new("compress_fast", file_name),
+            &original,
+            |b, original| b.iter(|| black_box(prs_compress_with_options(original, &options))),
+        );
     }
 
     group.finish();
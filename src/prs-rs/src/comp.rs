@@ -1,17 +1,47 @@
-use crate::prelude::{Allocator, Global};
+use crate::prelude::{AllocError, Allocator};
 
 use crate::{
-    impls::comp::{
-        comp_dict::{CompDict, MaxOffset},
-        compress::prs_compress,
+    error::PrsError,
+    impls::comp::compress::{
+        prs_compress as prs_compress_impl, try_prs_compress as try_prs_compress_impl,
     },
     MutablePointerSrc,
 };
+#[cfg(feature = "alloc")]
+use crate::{
+    impls::comp::{
+        comp_dict::{CompDict, MaxOffset, MAX_DATA_LEN},
+        compress::prs_compress_with_options as prs_compress_with_options_impl,
+    },
+    prelude::Global,
+    util::prs_calculate_max_compressed_size,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+
+#[cfg(feature = "std")]
+use crate::impls::comp::parallel::prs_compress_parallel as prs_compress_parallel_impl;
+use crate::impls::comp::scratch::{
+    prs_compress_scratch_size as prs_compress_scratch_size_impl,
+    prs_compress_with_scratch as prs_compress_with_scratch_impl,
+};
+
+pub use crate::impls::comp::compress::{CompressionLevel, CompressionOptions};
+pub use crate::impls::comp::compressor::PrsCompressor;
+#[cfg(feature = "std")]
+pub use crate::impls::comp::encoder::PrsEncoder;
+pub use crate::impls::comp::stats::{CompressionStats, OpcodeStats};
+#[cfg(feature = "alloc")]
+pub use crate::impls::comp::writer::PrsWriter;
 
 /// BENCHMARK ONLY, DO NOT USE
 #[doc(hidden)]
+#[cfg(feature = "alloc")]
 pub fn create_comp_dict(data: &[u8]) -> MaxOffset {
     unsafe {
+        // The compressor indexes one window at a time.
+        let data = &data[..data.len().min(MAX_DATA_LEN)];
         let mut dict = CompDict::new(data.len());
         dict.init(data, 0);
         dict.get_item(0, 0, u32::MAX as usize)[0]
@@ -34,12 +64,13 @@ pub fn create_comp_dict(data: &[u8]) -> MaxOffset {
 ///
 /// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
 /// and the remaining parameters are valid.
+#[cfg(feature = "alloc")]
 pub unsafe fn prs_compress_unsafe<T: MutablePointerSrc>(
     src: *const u8,
     src_len: usize,
     mut dest: T,
 ) -> usize {
-    prs_compress::<Global, Global>(src, dest.as_mut_ptr(), src_len, Global, Global)
+    prs_compress_impl::<Global, Global>(src, dest.as_mut_ptr(), src_len, Global, Global)
 }
 
 /// Compresses the given data in `source`, placing it in `destimation`.
@@ -72,7 +103,7 @@ pub unsafe fn prs_compress_unsafe_with_allocator<
     long_lived_allocator: L,
     short_lived_allocator: S,
 ) -> usize {
-    prs_compress::<L, S>(
+    prs_compress_impl::<L, S>(
         src,
         dest.as_mut_ptr(),
         src_len,
@@ -80,3 +111,272 @@ pub unsafe fn prs_compress_unsafe_with_allocator<
         short_lived_allocator,
     )
 }
+
+/// Same as [`prs_compress_unsafe_with_allocator`], but returns [`AllocError`] if either allocator
+/// fails to allocate, instead of panicking (or aborting).
+///
+/// Use this with fixed size allocators, such as arenas, which may run out of memory.
+///
+/// # Returns
+///
+/// Number of bytes written to `destination`. Nothing is written to `destination` on failure.
+///
+/// # Safety
+///
+/// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
+/// and the remaining parameters are valid.
+pub unsafe fn try_prs_compress_unsafe_with_allocator<
+    T: MutablePointerSrc,
+    L: Allocator + Copy,
+    S: Allocator + Copy,
+>(
+    src: *const u8,
+    src_len: usize,
+    mut dest: T,
+    long_lived_allocator: L,
+    short_lived_allocator: S,
+) -> Result<usize, AllocError> {
+    try_prs_compress_impl::<L, S>(
+        src,
+        dest.as_mut_ptr(),
+        src_len,
+        long_lived_allocator,
+        short_lived_allocator,
+    )
+}
+
+/// Compresses the given data in `src`, returning the compressed data in a new [`Vec`].
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+///
+/// # Returns
+///
+/// The compressed data.
+#[cfg(feature = "alloc")]
+pub fn prs_compress(src: &[u8]) -> Vec<u8> {
+    prs_compress_with_level(src, CompressionLevel::Greedy)
+}
+
+/// Compresses the given data in `src` with the given [`CompressionLevel`],
+/// returning the compressed data in a new [`Vec`].
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `level`: The strategy used to choose which matches to encode.
+///
+/// # Returns
+///
+/// The compressed data.
+#[cfg(feature = "alloc")]
+pub fn prs_compress_with_level(src: &[u8], level: CompressionLevel) -> Vec<u8> {
+    prs_compress_with_options(src, &CompressionOptions::new(level))
+}
+
+/// Compresses the given data in `src` with the given [`CompressionOptions`],
+/// returning the compressed data in a new [`Vec`].
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `options`: Options which control how the data is compressed.
+///
+/// # Returns
+///
+/// The compressed data.
+#[cfg(feature = "alloc")]
+pub fn prs_compress_with_options(src: &[u8], options: &CompressionOptions) -> Vec<u8> {
+    let mut dest = Vec::with_capacity(prs_calculate_max_compressed_size(src.len()));
+    unsafe {
+        let compressed_size = prs_compress_with_options_impl::<Global, Global>(
+            src.as_ptr(),
+            dest.as_mut_ptr(),
+            src.len(),
+            options,
+            Global,
+            Global,
+        );
+        dest.set_len(compressed_size);
+    }
+
+    dest
+}
+
+/// Compresses the given data in `src` with the given [`CompressionOptions`],
+/// returning the compressed data in a new [`Vec`], along with statistics about it.
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `options`: Options which control how the data is compressed.
+///
+/// # Returns
+///
+/// The compressed data, and a breakdown of the opcodes in it and of the time spent compressing it.
+/// See [`CompressionStats`].
+#[cfg(feature = "alloc")]
+pub fn prs_compress_with_stats(
+    src: &[u8],
+    options: &CompressionOptions,
+) -> (Vec<u8>, CompressionStats) {
+    PrsCompressor::with_options(*options).compress_with_stats(src)
+}
+
+/// Compresses the given data in `src` with the given [`CompressionOptions`] using multiple threads,
+/// returning the compressed data in a new [`Vec`].
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `options`: Options which control how the data is compressed.
+/// - `num_threads`: Maximum number of threads to use, e.g. [`std::thread::available_parallelism`].
+///
+/// # Returns
+///
+/// The compressed data, which can be decompressed as usual.
+///
+/// # Remarks
+///
+/// The data is split into segments which are compressed independently (with the preceding 0x1FFF bytes
+/// as look-behind), so the output may be slightly larger than that of [`prs_compress_with_options`].
+/// Each thread allocates its own compression buffers.
+#[cfg(feature = "std")]
+pub fn prs_compress_parallel(
+    src: &[u8],
+    options: &CompressionOptions,
+    num_threads: usize,
+) -> Vec<u8> {
+    prs_compress_parallel_impl(src, options, num_threads)
+}
+
+/// Compresses the given data in `src`, placing it in `dest`.
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `dest`: The buffer to write the compressed data to.
+///
+/// # Returns
+///
+/// Number of bytes written to `dest`, or [`PrsError::BufferTooSmall`] if the compressed
+/// data does not fit. Nothing is written to `dest` on failure.
+///
+/// # Remarks
+///
+/// If `dest` is at least [`prs_calculate_max_compressed_size`] bytes long, the data is compressed
+/// directly into `dest`. Otherwise it is compressed into a temporary buffer first, and copied over
+/// if it fits.
+#[cfg(feature = "alloc")]
+pub fn prs_compress_into(src: &[u8], dest: &mut [MaybeUninit<u8>]) -> Result<usize, PrsError> {
+    if dest.len() >= prs_calculate_max_compressed_size(src.len()) {
+        return Ok(unsafe {
+            prs_compress_impl::<Global, Global>(
+                src.as_ptr(),
+                dest.as_mut_ptr() as *mut u8,
+                src.len(),
+                Global,
+                Global,
+            )
+        });
+    }
+
+    let compressed = prs_compress(src);
+    if compressed.len() > dest.len() {
+        return Err(PrsError::BufferTooSmall {
+            required: compressed.len(),
+        });
+    }
+
+    for (dst, &byte) in dest.iter_mut().zip(compressed.iter()) {
+        dst.write(byte);
+    }
+
+    Ok(compressed.len())
+}
+
+/// Returns the number of bytes of scratch memory [`prs_compress_with_scratch`] needs to compress
+/// `src_len` bytes of data with the given [`CompressionOptions`].
+///
+/// Parameters
+///
+/// - `src_len`: Length of the data to compress.
+/// - `options`: Options which control how the data is compressed.
+///
+/// # Remarks
+///
+/// The size includes padding for aligning the buffers inside the scratch memory, so the scratch
+/// memory may start at any address. [`CompressionLevel::Sega`] needs no scratch memory, and neither
+/// do [`CompressionLevel::Greedy`] and [`CompressionLevel::Lazy`] for inputs under 4 KiB.
+pub fn prs_compress_scratch_size(src_len: usize, options: &CompressionOptions) -> usize {
+    prs_compress_scratch_size_impl(src_len, options)
+}
+
+/// Compresses the given data in `src`, placing it in `dest`, without allocating any memory.
+/// The dictionary and all other buffers used by the compressor are placed in `scratch` instead.
+///
+/// Parameters
+///
+/// - `src`: The data to compress.
+/// - `dest`: The buffer to write the compressed data to. Must be at least
+///   [`prs_calculate_max_compressed_size`] bytes long.
+/// - `options`: Options which control how the data is compressed.
+/// - `scratch`: Memory used by the compressor, at least [`prs_compress_scratch_size`] bytes long.
+///   Its contents are overwritten.
+///
+/// # Returns
+///
+/// Number of bytes written to `dest`, [`PrsError::ScratchTooSmall`] if `scratch` is too small,
+/// or [`PrsError::BufferTooSmall`] if `dest` is smaller than [`prs_calculate_max_compressed_size`].
+///
+/// # Remarks
+///
+/// Unlike [`prs_compress_into`], `dest` is rejected if it is smaller than
+/// [`prs_calculate_max_compressed_size`], even if the compressed data would have fit;
+/// there is no temporary buffer to compress into first.
+///
+/// Useful for `no_std` targets without a global allocator (with the `alloc` feature disabled).
+/// `scratch` can be reused for any data with the same or smaller length.
+pub fn prs_compress_with_scratch(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    options: &CompressionOptions,
+    scratch: &mut [u8],
+) -> Result<usize, PrsError> {
+    prs_compress_with_scratch_impl(src, dest, options, scratch)
+}
+
+/// Compresses the given data in `src`, placing it in `dest`, allowing copies to reference
+/// data in `dictionary`.
+///
+/// Useful when compressing many files which share common data (e.g. headers); that data
+/// can be passed as `dictionary` instead of being stored in every file.
+///
+/// Parameters
+///
+/// - `dictionary`: Data which is treated as if it came directly before `src`.
+///   Only the last 0x1FFF bytes (the max distance of a copy) are used.
+/// - `src`: The data to compress.
+/// - `dest`: The buffer to write the compressed data to.
+///
+/// # Returns
+///
+/// Number of bytes written to `dest`, or [`PrsError::BufferTooSmall`] if the compressed
+/// data does not fit. Nothing is written to `dest` on failure.
+///
+/// # Remarks
+///
+/// The data must be decompressed with the same dictionary,
+/// see [`crate::decomp::prs_decompress_with_dictionary`].
+///
+/// To compress many files, use [`PrsCompressor::compress_with_dictionary`] instead,
+/// which reuses its buffers between calls.
+#[cfg(feature = "alloc")]
+pub fn prs_compress_with_dictionary(
+    dictionary: &[u8],
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+) -> Result<usize, PrsError> {
+    PrsCompressor::new().compress_with_dictionary(dictionary, src, dest)
+}
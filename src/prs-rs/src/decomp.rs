@@ -1,7 +1,28 @@
+#[cfg(feature = "alloc")]
+use crate::impls::decomp::estimate::prs_calculate_decompressed_size_checked;
 use crate::{
-    impls::decomp::{decompress::prs_decompress, estimate::prs_calculate_decompressed_size_impl},
+    error::PrsError,
+    impls::decomp::{
+        decompress::{
+            prs_decompress as prs_decompress_impl, prs_decompress_checked,
+            prs_decompress_checked_with_dictionary,
+            prs_decompress_in_place as prs_decompress_in_place_impl,
+        },
+        estimate::{
+            prs_calculate_decompressed_size_impl, prs_calculate_in_place_margin_checked,
+            prs_scan_stream as prs_scan_stream_impl,
+        },
+    },
     MutablePointerSrc, ReadOnlyPointerSrc,
 };
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+pub use crate::impls::decomp::estimate::{PrsStreams, StreamInfo};
+pub use crate::impls::decomp::incremental::{DecodeStatus, PrsIncrementalDecoder};
+
+#[cfg(feature = "std")]
+pub use crate::impls::decomp::decoder::PrsDecoder;
 
 /// Decodes the compressed data at `source` without performing the actual decompression.
 ///
@@ -24,6 +45,39 @@ pub unsafe fn prs_calculate_decompressed_size<S: ReadOnlyPointerSrc>(src: S) ->
     prs_calculate_decompressed_size_impl(src.as_ptr())
 }
 
+/// Determines both the compressed and decompressed length of the PRS stream at the start of `src`,
+/// with bounds checking.
+///
+/// # Parameters
+///
+/// - `src`: The compressed data. May contain additional data after the stream.
+///
+/// # Returns
+///
+/// The lengths of the stream, or an error if `src` ends before the stream's terminator.
+///
+/// # Remarks
+///
+/// Useful for archives which store PRS streams back to back without recording their compressed size;
+/// `src[info.compressed_len..]` is where the next stream starts. See also [`prs_scan_streams`].
+pub fn prs_scan_stream(src: &[u8]) -> Result<StreamInfo, PrsError> {
+    prs_scan_stream_impl(src)
+}
+
+/// Iterates over PRS streams stored back to back in `src`, with bounds checking.
+///
+/// # Parameters
+///
+/// - `src`: The compressed data; one or more PRS streams, with no padding between them.
+///
+/// # Returns
+///
+/// An iterator yielding the compressed data of each stream, along with its [`StreamInfo`].
+/// The iterator ends at the end of `src`, or after yielding the first error.
+pub fn prs_scan_streams(src: &[u8]) -> PrsStreams<'_> {
+    PrsStreams::new(src)
+}
+
 /// Decompresses PRS compressed data, in an unsafe manner, without any error handling.
 ///
 /// # Parameters
@@ -54,5 +108,120 @@ pub unsafe fn prs_decompress_unsafe<S: ReadOnlyPointerSrc, T: MutablePointerSrc>
     src: S,
     mut dest: T,
 ) -> usize {
-    prs_decompress(src.as_ptr(), dest.as_mut_ptr())
+    prs_decompress_impl(src.as_ptr(), dest.as_mut_ptr())
+}
+
+/// Decompresses PRS compressed data, with bounds checking.
+///
+/// # Parameters
+///
+/// - `src`: The compressed data.
+/// - `dest`: The buffer to decompress the data into.
+///
+/// # Returns
+///
+/// The length of the decompressed data, or an error if `src` is malformed or
+/// the decompressed data does not fit in `dest`.
+///
+/// # Remarks
+///
+/// This is slower than [`prs_decompress_unsafe`], but will never read outside of `src` or
+/// write outside of `dest`. Use this when dealing with untrusted input.
+///
+/// Errors report the offset in `src` where the problem was encountered.
+pub fn prs_decompress(src: &[u8], dest: &mut [u8]) -> Result<usize, PrsError> {
+    prs_decompress_checked(src, dest)
+}
+
+/// Decompresses PRS compressed data which was compressed with a preset dictionary,
+/// with bounds checking.
+///
+/// # Parameters
+///
+/// - `dictionary`: The dictionary the data was compressed with.
+///   See [`crate::comp::prs_compress_with_dictionary`].
+/// - `src`: The compressed data.
+/// - `dest`: The buffer to decompress the data into.
+///
+/// # Returns
+///
+/// The length of the decompressed data, or an error if `src` is malformed or
+/// the decompressed data does not fit in `dest`.
+///
+/// # Remarks
+///
+/// Copies may reference data up to 0x1FFF bytes before the start of `dest`, which is read
+/// from the end of `dictionary`. Only the decompressed data itself is written to `dest`.
+pub fn prs_decompress_with_dictionary(
+    dictionary: &[u8],
+    src: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, PrsError> {
+    prs_decompress_checked_with_dictionary(dictionary, src, dest)
+}
+
+/// Decompresses PRS compressed data stored at the end of `buf` into the start of `buf`, with bounds checking.
+///
+/// This allows reading a compressed file straight into the end of the buffer for its decompressed
+/// data, instead of needing separate buffers for both.
+///
+/// # Parameters
+///
+/// - `buf`: Buffer containing the compressed data starting at `compressed_start`.
+///   Must be at least `decompressed_len + margin` bytes long, where `margin` is
+///   calculated by [`prs_calculate_in_place_margin`].
+/// - `compressed_start`: Offset of the compressed data in `buf`.
+///
+/// # Returns
+///
+/// The length of the decompressed data at the start of `buf`, or an error if the compressed data is
+/// malformed, or [`PrsError::InPlaceOverrun`] if the output would overwrite compressed data which
+/// was not read yet.
+///
+/// # Remarks
+///
+/// If the compressed data is placed at the very end of a buffer of `decompressed_len + margin` bytes,
+/// decompression is guaranteed not to overrun.
+pub fn prs_decompress_in_place(buf: &mut [u8], compressed_start: usize) -> Result<usize, PrsError> {
+    prs_decompress_in_place_impl(buf, compressed_start)
+}
+
+/// Calculates the safety margin needed to decompress `src` in place using [`prs_decompress_in_place`].
+///
+/// # Parameters
+///
+/// - `src`: The compressed data.
+///
+/// # Returns
+///
+/// The number of bytes the buffer needs on top of the decompressed length, when the compressed data is
+/// placed at the end of the buffer; or an error if `src` is malformed.
+///
+/// # Remarks
+///
+/// The decompressed length can be obtained together with the compressed length using [`prs_scan_stream`].
+pub fn prs_calculate_in_place_margin(src: &[u8]) -> Result<usize, PrsError> {
+    prs_calculate_in_place_margin_checked(src)
+}
+
+/// Decompresses PRS compressed data into a newly allocated [`Vec`], with bounds checking.
+///
+/// # Parameters
+///
+/// - `src`: The compressed data.
+///
+/// # Returns
+///
+/// The decompressed data, or an error if `src` is malformed.
+///
+/// # Remarks
+///
+/// The size of the output is determined by walking the compressed data before decompressing,
+/// see [`prs_decompress`] for more details.
+#[cfg(feature = "alloc")]
+pub fn prs_decompress_to_vec(src: &[u8]) -> Result<Vec<u8>, PrsError> {
+    let mut dest = vec![0_u8; prs_calculate_decompressed_size_checked(src)?];
+    let decompressed_size = prs_decompress_checked(src, &mut dest)?;
+    debug_assert_eq!(decompressed_size, dest.len());
+    Ok(dest)
 }
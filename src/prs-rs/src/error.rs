@@ -0,0 +1,89 @@
+use core::fmt;
+
+/// Errors returned by the safe (bounds checked) APIs of this library.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PrsError {
+    /// The compressed data ended before the terminator (`01` + `0x0000`) was reached.
+    UnexpectedEndOfInput {
+        /// Offset in the compressed input where more data was expected.
+        input_offset: usize,
+    },
+
+    /// A copy opcode references data before the start of the decompressed output.
+    InvalidBackReference {
+        /// Offset in the compressed input of the copy opcode's data bytes.
+        input_offset: usize,
+    },
+
+    /// The decompressed data does not fit in the destination buffer.
+    OutputOverflow {
+        /// Offset in the compressed input of the opcode which would have overflowed the output.
+        input_offset: usize,
+    },
+
+    /// During in place decompression, the decompressed data would overwrite compressed data which
+    /// was not read yet; the compressed data needs to be placed further towards the end of the buffer.
+    InPlaceOverrun {
+        /// Offset in the compressed input of the opcode which would have overwritten unread data.
+        input_offset: usize,
+    },
+
+    /// The compressed data does not fit in the destination buffer.
+    BufferTooSmall {
+        /// Number of bytes required to store the compressed data.
+        required: usize,
+    },
+
+    /// The scratch memory passed to [`crate::comp::prs_compress_with_scratch`] is too small.
+    ScratchTooSmall {
+        /// Number of bytes of scratch memory required, see [`crate::comp::prs_compress_scratch_size`].
+        required: usize,
+    },
+
+    /// A token passed to [`crate::comp::PrsWriter`] can't be encoded, e.g. because its offset or length
+    /// is out of range for its opcode.
+    InvalidToken {
+        /// Offset in the decompressed data where the token would have started.
+        decompressed_offset: usize,
+    },
+}
+
+impl fmt::Display for PrsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrsError::UnexpectedEndOfInput { input_offset } => write!(
+                f,
+                "unexpected end of compressed data at input offset {input_offset}"
+            ),
+            PrsError::InvalidBackReference { input_offset } => write!(
+                f,
+                "copy references data before start of output at input offset {input_offset}"
+            ),
+            PrsError::OutputOverflow { input_offset } => write!(
+                f,
+                "decompressed data overflows destination buffer at input offset {input_offset}"
+            ),
+            PrsError::InPlaceOverrun { input_offset } => write!(
+                f,
+                "decompressed data overwrites unread compressed data at input offset {input_offset}"
+            ),
+            PrsError::BufferTooSmall { required } => write!(
+                f,
+                "destination buffer too small, {required} bytes are required"
+            ),
+            PrsError::ScratchTooSmall { required } => {
+                write!(f, "scratch memory too small, {required} bytes are required")
+            }
+            PrsError::InvalidToken {
+                decompressed_offset,
+            } => write!(
+                f,
+                "token cannot be encoded at decompressed offset {decompressed_offset}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PrsError {}
@@ -42,12 +42,12 @@ pub unsafe extern "C" fn prs_compress(
 ///
 /// # Remarks
 ///
-/// A properly compressed PRS file has a theoretical maximum size of 1.125 times the size of the
-/// original input. i.e. (1 byte for every 8 bytes of input).
+/// In the worst case, i.e. data which doesn't compress at all, every byte is written as a literal,
+/// which takes 1 control bit and 1 byte. The file is then terminated by the 2 bit `01` opcode,
+/// followed by 2 zero bytes.
 ///
-/// Up to 2 bytes may be added to that in addition, namely via:
-/// - Rounding file to next byte
-/// - Having to write 00 opcode after a compressed sequence of bytes to terminate.
+/// That is `source_len` bytes, plus `ceil((source_len + 2) / 8)` control bytes, plus 2 bytes,
+/// which is at most `source_len + source_len / 8 + 4`.
 #[no_mangle]
 #[inline(never)]
 pub extern "C" fn prs_calculate_max_compressed_size(source_len: usize) -> usize {
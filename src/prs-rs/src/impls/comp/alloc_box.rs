@@ -0,0 +1,80 @@
+use crate::prelude::{AllocError, Allocator, Layout};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{drop_in_place, NonNull};
+
+/// Minimal `Box<T, A>`, for memory owned by an [`Allocator`].
+///
+/// The `Box` of `allocator-api2` (and `std`) requires the `alloc` crate, which in turn requires a
+/// global allocator to be linked. This doesn't, so [`crate::comp::prs_compress_with_scratch`]
+/// can be used without one.
+pub(crate) struct AllocBox<T: ?Sized, A: Allocator> {
+    ptr: NonNull<T>,
+    allocator: A,
+}
+
+impl<T: ?Sized, A: Allocator> AllocBox<T, A> {
+    /// Takes ownership of `ptr`, which is freed with `allocator` on drop.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null, point to an initialized `T`, and have been allocated by `allocator`
+    /// with the layout of that `T` (or `allocator` must not free memory, e.g. [`super::scratch::NoAlloc`]).
+    #[inline]
+    pub(crate) unsafe fn from_raw_in(ptr: *mut T, allocator: A) -> Self {
+        Self {
+            ptr: NonNull::new_unchecked(ptr),
+            allocator,
+        }
+    }
+}
+
+impl<T, A: Allocator> AllocBox<[T], A> {
+    /// Allocates a slice of `len` elements, with all bytes set to zero.
+    ///
+    /// # Safety
+    ///
+    /// All zero bytes must be a valid `T`.
+    pub(crate) unsafe fn try_new_zeroed_slice_in(
+        len: usize,
+        allocator: A,
+    ) -> Result<Self, AllocError> {
+        let layout = Layout::array::<T>(len).map_err(|_| AllocError)?;
+        let ptr = allocator.allocate_zeroed(layout)?.as_ptr() as *mut T;
+        Ok(Self::from_raw_in(
+            core::ptr::slice_from_raw_parts_mut(ptr, len),
+            allocator,
+        ))
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for AllocBox<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let layout = Layout::for_value(self.ptr.as_ref());
+            drop_in_place(self.ptr.as_ptr());
+            if layout.size() != 0 {
+                self.allocator.deallocate(self.ptr.cast(), layout);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Deref for AllocBox<T, A> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized, A: Allocator> DerefMut for AllocBox<T, A> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+// Same as `Box`; the contents are uniquely owned.
+unsafe impl<T: ?Sized + Send, A: Allocator + Send> Send for AllocBox<T, A> {}
+unsafe impl<T: ?Sized + Sync, A: Allocator + Sync> Sync for AllocBox<T, A> {}
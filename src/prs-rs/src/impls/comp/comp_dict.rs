@@ -1,12 +1,23 @@
-use crate::prelude::{Allocator, Box, Global, Layout};
-use core::ptr::{write, NonNull};
+use super::alloc_box::AllocBox;
+use super::scratch::NoAlloc;
+#[cfg(feature = "alloc")]
+use crate::prelude::Global;
+use crate::prelude::{AllocError, Allocator, Layout};
+use core::ptr::{copy, slice_from_raw_parts_mut, write, NonNull};
 use core::slice;
+use core::time::Duration;
 use core::{mem::size_of, ptr::read_unaligned};
 
-pub(crate) type MaxOffset = u32;
-type FreqCountType = u32;
+/// A location within the window, or an index into the offsets section of [`CompDict::buf`].
+pub(crate) type MaxOffset = u16;
+type FreqCountType = u16;
 const MAX_U16: usize = 65536;
-const ALLOC_ALIGNMENT: usize = 64; // x86 cache line
+
+/// Maximum length of data a [`CompDict`] can be created for.
+///
+/// Every location within the data (and the number of locations) then fits in a `u16`.
+pub const MAX_DATA_LEN: usize = MAX_U16;
+pub(crate) const ALLOC_ALIGNMENT: usize = 64; // x86 cache line
 
 // Round up to next multiple of ALLOC_ALIGNMENT
 const DICTIONARY_PADDING: usize =
@@ -14,17 +25,31 @@ const DICTIONARY_PADDING: usize =
 
 const ENTRY_SECTION_LEN: usize = size_of::<[CompDictEntry; MAX_U16]>();
 
+// Scratch buffers used during init; both are multiples of ALLOC_ALIGNMENT.
+const INSERT_INDICES_LEN: usize = size_of::<[MaxOffset; MAX_U16]>();
+const FREQ_TABLE_LEN: usize = size_of::<[FreqCountType; MAX_U16]>();
+
 /// Dictionary for PRS compression.
 ///
 /// This dictionary stores the locations of every single possible place that a specified 2-byte sequence
 /// can be found, with the 2 byte combination being the dictionary 'key'. The values (locations) are
-/// stored inside a shared buffer, where [`CompDictEntry`] dictates the file offsets of the locations
+/// stored inside a shared buffer, where [`CompDictEntry`] dictates the indices of the locations
 /// which start with this 2 byte combination. The items are stored in ascending order.
 ///
+/// Locations are stored relative to the start of the data the dictionary was initialized with
+/// ([`CompDict::window_start`]). As the data is at most [`MAX_DATA_LEN`] bytes, they fit in a
+/// `u16` regardless of where in the input the window is. The same goes for the indices
+/// in [`CompDictEntry`], which keeps the whole dictionary small enough to stay in L2 cache.
+///
 /// When the compressor is looking for longest match at given address, it will read the 2 bytes at the
 /// address and use that as key [`CompDict::get_item`]. Then the offsets inside the returned entry
 /// will be used to greatly speed up search.
-pub struct CompDict<L: Allocator + Copy = Global, S: Allocator + Copy = Global> {
+pub struct CompDict<
+    #[cfg(feature = "alloc")] L: Allocator + Copy = Global,
+    #[cfg(feature = "alloc")] S: Allocator + Copy = Global,
+    #[cfg(not(feature = "alloc"))] L: Allocator + Copy,
+    #[cfg(not(feature = "alloc"))] S: Allocator + Copy,
+> {
     /// Our memory allocation is here.
     /// Layout:
     /// - [CompDictEntry; MAX_U16] (dict), constant size
@@ -32,7 +57,21 @@ pub struct CompDict<L: Allocator + Copy = Global, S: Allocator + Copy = Global>
     buf: NonNull<u8>,
     alloc_length: usize, // length of data that 'dict' and 'offsets' were made with
     long_lived_allocator: L,
-    short_lived_allocator: S,
+    /// Scratch buffer used during [`CompDict::init`].
+    /// Stores the index (in the offsets section) where we need to insert the next offset for a given 2 byte sequence.
+    insert_indices: AllocBox<[MaxOffset; MAX_U16], S>,
+    /// Scratch buffer used during [`CompDict::init`].
+    /// Stores the number of occurrences of each 2 byte sequence. Zeroed outside of [`CompDict::init`].
+    freq_table: AllocBox<[FreqCountType], S>,
+    /// Offset of the data passed to the last [`CompDict::init`] or [`CompDict::slide`] call, within the whole input.
+    window_start: usize,
+    /// Length of the data passed to the last [`CompDict::init`] or [`CompDict::slide`] call.
+    window_len: usize,
+    /// Total time spent in [`CompDict::init`]. Only measured with the `std` feature.
+    init_time: Duration,
+    /// Whether to measure [`CompDict::init_time`], see [`CompDict::set_collect_stats`].
+    #[cfg(feature = "std")]
+    collect_stats: bool,
 }
 
 impl<L: Allocator + Copy, S: Allocator + Copy> Drop for CompDict<L, S> {
@@ -47,8 +86,8 @@ impl<L: Allocator + Copy, S: Allocator + Copy> Drop for CompDict<L, S> {
 
 /// An entry in [Compression Dictionary][`CompDict`].
 ///
-/// This has pointer to current 'last min offset' [`CompDictEntry::last_read_item`] in [`CompDict::buf`]
-/// allocation, and pointer to last offset for the current 2 byte key.
+/// This has the index of the current 'last min offset' [`CompDictEntry::last_read_item`] in the offsets
+/// section of [`CompDict::buf`], and the index after the last offset for the current 2 byte key.
 ///
 /// Last min offset [`CompDictEntry::last_read_item`] is advanced as items are sequentially read,
 /// i.e. when [`CompDict::get_item`] is called. This offset corresponds to the first item which had
@@ -59,12 +98,12 @@ impl<L: Allocator + Copy, S: Allocator + Copy> Drop for CompDict<L, S> {
 #[derive(Clone)]
 #[allow(rustdoc::private_intra_doc_links)]
 pub struct CompDictEntry {
-    /// Address of the last minimum offset from previous call to [`CompDict::get_item`].
-    last_read_item: *mut MaxOffset,
-    /// Address of the last maximum offset from previous call to [`CompDict::get_item`].
-    last_read_item_max: *mut MaxOffset,
-    /// Item after last item within the offsets section of the [`CompDict::buf`].
-    last_item: *mut MaxOffset,
+    /// Index of the last minimum offset from previous call to [`CompDict::get_item`].
+    last_read_item: MaxOffset,
+    /// Index of the last maximum offset from previous call to [`CompDict::get_item`].
+    last_read_item_max: MaxOffset,
+    /// Index of the item after last item within the offsets section of the [`CompDict::buf`].
+    last_item: MaxOffset,
 }
 
 impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
@@ -73,24 +112,62 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
     /// # Parameters
     ///
     /// - `data_len`: The length of the data that will be used to initialize the dictionary.
+    ///   At most [`MAX_DATA_LEN`].
     /// - `long_lived_allocator`: The allocator to use for long-lived memory allocation.
     /// - `short_lived_allocator`: The allocator to use for short-lived memory allocation.
+    ///
+    /// # Panics
+    ///
+    /// If either allocator fails to allocate, or `data_len` is greater than [`MAX_DATA_LEN`];
+    /// see [`CompDict::try_new_in`] for a non-panicking version.
     #[inline(always)]
     pub fn new_in(data_len: usize, long_lived_allocator: L, short_lived_allocator: S) -> Self {
+        Self::try_new_in(data_len, long_lived_allocator, short_lived_allocator).unwrap()
+    }
+
+    /// Same as [`CompDict::new_in`], but returns [`AllocError`] if either allocator fails to allocate,
+    /// or if `data_len` is greater than [`MAX_DATA_LEN`].
+    ///
+    /// All memory used by the dictionary is allocated here; [`CompDict::init`] does not allocate.
+    pub fn try_new_in(
+        data_len: usize,
+        long_lived_allocator: L,
+        short_lived_allocator: S,
+    ) -> Result<Self, AllocError> {
+        if data_len > MAX_DATA_LEN {
+            return Err(AllocError);
+        }
+
         unsafe {
             // constant
             let offset_section_len = size_of::<MaxOffset>() * data_len;
             let alloc_size = ENTRY_SECTION_LEN + DICTIONARY_PADDING + offset_section_len;
 
+            // The scratch buffers are only needed during init, but we keep them around,
+            // as the dictionary is re-initialized for every window.
+            // They are allocated first, so they are freed by `AllocBox` if a later allocation fails.
+            let freq_table = AllocBox::try_new_zeroed_slice_in(MAX_U16, short_lived_allocator)?;
+
+            let alloc = short_lived_allocator
+                .allocate(Layout::new::<[MaxOffset; MAX_U16]>())?
+                .as_ptr() as *mut [MaxOffset; MAX_U16];
+            let insert_indices = AllocBox::from_raw_in(alloc, short_lived_allocator);
+
             let layout = Layout::from_size_align_unchecked(alloc_size, ALLOC_ALIGNMENT);
-            let buf = long_lived_allocator.allocate(layout).unwrap();
+            let buf = long_lived_allocator.allocate(layout)?;
 
-            CompDict {
+            Ok(CompDict {
                 buf: NonNull::new_unchecked(buf.as_ptr() as *mut u8),
                 alloc_length: alloc_size,
                 long_lived_allocator,
-                short_lived_allocator,
-            }
+                insert_indices,
+                freq_table,
+                window_start: 0,
+                window_len: 0,
+                init_time: Duration::ZERO,
+                #[cfg(feature = "std")]
+                collect_stats: false,
+            })
         }
     }
 
@@ -99,7 +176,7 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
     /// # Parameters
     ///
     /// - `data`: The data to create the dictionary from.
-    /// - `offset`: The offset to add to the offsets in the dictionary.
+    /// - `offset`: The offset of `data` within the whole input, see [`CompDict::window_start`].
     ///
     /// # Safety
     ///
@@ -107,81 +184,200 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
     /// the `CompDict` has been properly allocated with enough space for `data`.
     #[inline(always)]
     pub unsafe fn init(&mut self, data: &[u8], offset: usize) {
+        #[cfg(feature = "std")]
+        let start_time = self.collect_stats.then(std::time::Instant::now);
+
         let dict_entry_ptr = self.buf.as_ptr() as *mut CompDictEntry;
-        let max_ofs_ptr =
-            self.buf
-                .as_ptr()
-                .add(ENTRY_SECTION_LEN + DICTIONARY_PADDING) as *mut MaxOffset;
+
+        self.window_start = offset;
+        self.window_len = data.len();
+        self.fill_frequency_table(data);
 
         // We will use this later to populate the dictionary.
-        // The `dict_insert_entry_ptrs` is a buffer which stores the pointer to the current location
+        // The `dict_insert_indices` is a buffer which stores the index of the current location
         // where we need to insert the offset for a given 2 byte sequence (hence length MAX_U16).
-        let alloc = self
-            .short_lived_allocator
-            .allocate(Layout::new::<[*mut MaxOffset; MAX_U16]>())
-            .unwrap()
-            .as_ptr() as *mut [*mut MaxOffset; MAX_U16];
-
-        let mut dict_insert_entry_ptrs =
-            Box::<[*mut MaxOffset; MAX_U16], S>::from_raw_in(alloc, self.short_lived_allocator);
-
-        // dict_insert_entry_ptrs is now a Box, so it will be deallocated when it goes out of scope.
+        let dict_insert_indices = &mut *self.insert_indices;
 
         // Initialize all CompDictEntries
-        let freq_table = self.create_frequency_table(data);
-        let mut cur_ofs_addr = max_ofs_ptr;
+        let mut cur_ofs_idx: MaxOffset = 0;
         let mut cur_dict_entry = dict_entry_ptr;
-        let mut cur_freq_tbl_entry = freq_table.as_ptr();
-        let mut cur_ofs_insert_ptr = dict_insert_entry_ptrs.as_mut_ptr();
+        let mut cur_freq_tbl_entry = self.freq_table.as_mut_ptr();
+        let mut cur_ofs_insert_idx = dict_insert_indices.as_mut_ptr();
         let max_dict_entry = cur_dict_entry.add(MAX_U16);
 
         // This loop initializes each CompDictEntry (ies) based on the frequency table.
-        // It sets up the pointers for where the offsets for each 2-byte sequence will be stored.
-        // This also populates `dict_insert_entry_ptrs` (via `cur_ofs_insert_ptr`) setting each
-        // entry to the value of `cur_ofs_addr` (the current offset address).
+        // It sets up the indices where the offsets for each 2-byte sequence will be stored.
+        // This also populates `dict_insert_indices` (via `cur_ofs_insert_idx`) setting each
+        // entry to the value of `cur_ofs_idx` (the current offset index).
+        // The frequency table is zeroed as we go, so it is ready for the next init.
         while cur_dict_entry < max_dict_entry {
             let num_items = *cur_freq_tbl_entry;
-            *cur_ofs_insert_ptr = cur_ofs_addr;
+            *cur_freq_tbl_entry = 0;
+            *cur_ofs_insert_idx = cur_ofs_idx;
 
             write(
                 cur_dict_entry,
                 CompDictEntry {
-                    last_read_item: cur_ofs_addr,
-                    last_read_item_max: cur_ofs_addr,
-                    last_item: cur_ofs_addr.add(num_items as usize),
+                    last_read_item: cur_ofs_idx,
+                    last_read_item_max: cur_ofs_idx,
+                    last_item: cur_ofs_idx + num_items,
                 },
             );
 
-            cur_ofs_addr = cur_ofs_addr.add(num_items as usize);
+            cur_ofs_idx += num_items;
             cur_freq_tbl_entry = cur_freq_tbl_entry.add(1);
             cur_dict_entry = cur_dict_entry.add(1);
-            cur_ofs_insert_ptr = cur_ofs_insert_ptr.add(1);
+            cur_ofs_insert_idx = cur_ofs_insert_idx.add(1);
+        }
+
+        self.insert_offsets(data, 0);
+
+        #[cfg(feature = "std")]
+        if let Some(start_time) = start_time {
+            self.init_time += start_time.elapsed();
+        }
+    }
+
+    /// Moves the [`CompDict`] forward to the given data and offset, which must overlap the data it
+    /// was last initialized (or slid) with.
+    ///
+    /// Locations which are still within `data` are kept, those before it are evicted, and only the
+    /// data past the end of the previous window is indexed. This gives the same result as
+    /// [`CompDict::init`], without re-reading the overlapping data.
+    ///
+    /// If the windows don't overlap, this falls back to [`CompDict::init`].
+    ///
+    /// # Parameters
+    ///
+    /// - `data`: The data to create the dictionary from.
+    /// - `offset`: The offset of `data` within the whole input, see [`CompDict::window_start`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`CompDict::init`]. In addition, `data` must come from the same input as the data the
+    /// dictionary was last initialized with, i.e. the overlapping bytes must be the same.
+    pub unsafe fn slide(&mut self, data: &[u8], offset: usize) {
+        // Keys (2 byte sequences) start at all but the last byte of a window.
+        let old_keys_end = (self.window_start + self.window_len).saturating_sub(1);
+        if offset < self.window_start
+            || offset >= old_keys_end
+            || offset + data.len() < old_keys_end + 1
+        {
+            self.init(data, offset);
+            return;
+        }
+
+        #[cfg(feature = "std")]
+        let start_time = self.collect_stats.then(std::time::Instant::now);
+
+        let dict_entry_ptr = self.buf.as_ptr() as *mut CompDictEntry;
+        let offsets = self.offsets_ptr();
+
+        // Number of keys which are in both windows; their locations are kept.
+        let kept_keys = old_keys_end - offset;
+        let evict_below = (offset - self.window_start) as MaxOffset;
+
+        // Pass 1: For each key, move the kept locations (rebased to the new window) to the front of
+        // the offsets section, in key order. Nothing is overwritten before it is read, because
+        // the compacted locations are always at or before their old position.
+        // Temporarily, each entry's `last_read_item` and `last_item` point at its compacted locations.
+        let mut old_start = 0;
+        let mut compacted = 0;
+        for key in 0..MAX_U16 {
+            let entry = &mut *dict_entry_ptr.add(key);
+            let old_end = entry.last_item as usize;
+
+            // Locations are ascending, so the kept ones are at the end.
+            let mut first_kept = old_end;
+            while first_kept > old_start && *offsets.add(first_kept - 1) >= evict_below {
+                first_kept -= 1;
+            }
+
+            let num_kept = old_end - first_kept;
+            for x in 0..num_kept {
+                *offsets.add(compacted + x) = *offsets.add(first_kept + x) - evict_below;
+            }
+
+            entry.last_read_item = compacted as MaxOffset;
+            compacted += num_kept;
+            entry.last_item = compacted as MaxOffset;
+            old_start = old_end;
         }
 
-        // The rest of the function is dedicated to actually populating the dictionary with offsets.
+        // Count the keys in the new part of the window.
+        self.fill_frequency_table(&data[kept_keys..]);
+
+        // Pass 2: Move the kept locations to their final position, making room for the new ones.
+        // Going backwards, the final position is always at or after the compacted position, and past
+        // the compacted locations of all remaining keys.
+        let dict_insert_indices = &mut *self.insert_indices;
+        let mut final_end = data.len().saturating_sub(1);
+        for key in (0..MAX_U16).rev() {
+            let entry = &mut *dict_entry_ptr.add(key);
+            let num_kept = (entry.last_item - entry.last_read_item) as usize;
+            let num_new = self.freq_table[key] as usize;
+            self.freq_table[key] = 0;
+
+            let final_start = final_end - num_kept - num_new;
+            copy(
+                offsets.add(entry.last_read_item as usize),
+                offsets.add(final_start),
+                num_kept,
+            );
+            dict_insert_indices[key] = (final_start + num_kept) as MaxOffset;
+            write(
+                entry,
+                CompDictEntry {
+                    last_read_item: final_start as MaxOffset,
+                    last_read_item_max: final_start as MaxOffset,
+                    last_item: final_end as MaxOffset,
+                },
+            );
+
+            final_end = final_start;
+        }
+        debug_assert_eq!(final_end, 0);
+
+        self.window_start = offset;
+        self.window_len = data.len();
+        self.insert_offsets(data, kept_keys);
+
+        #[cfg(feature = "std")]
+        if let Some(start_time) = start_time {
+            self.init_time += start_time.elapsed();
+        }
+    }
+
+    /// Inserts the locations of the keys in `data`, starting from `start`, at the insert locations
+    /// of their entries (`insert_indices`).
+    #[inline(always)]
+    unsafe fn insert_offsets(&mut self, data: &[u8], start: usize) {
+        let offsets = self.offsets_ptr();
+        let dict_insert_indices = &mut *self.insert_indices;
+
         // Here we do the following:
         // - Read Each 2 Byte Sequence
         // - Use 2 Byte Sequence as Key
-        // - Gets insert location via `dict_insert_entry_ptrs` (**insert_entry_ptr)
-        // - Advance insert location for given key (*insert_entry_ptr)
+        // - Gets insert location via `dict_insert_indices` (offsets[*insert_idx])
+        // - Advance insert location for given key (*insert_idx)
 
         // Iterate over the data, and add each 2-byte sequence to the dictionary.
         #[cfg(not(target_pointer_width = "64"))]
         {
             let data_ptr_start = data.as_ptr();
-            let mut data_ptr = data.as_ptr();
+            let mut data_ptr = data.as_ptr().add(start);
             let data_ptr_max = data.as_ptr().add(data.len().saturating_sub(1));
-            debug_assert!(data.len() as MaxOffset <= MaxOffset::MAX);
+            debug_assert!(data.len() <= MAX_DATA_LEN);
 
             while data_ptr < data_ptr_max {
                 let key = read_unaligned(data_ptr as *const u16);
-                let insert_entry_ptr = dict_insert_entry_ptrs.as_mut_ptr().add(key as usize);
+                let insert_idx = dict_insert_indices.as_mut_ptr().add(key as usize);
 
                 // Insert the offset into the dictionary
-                **insert_entry_ptr = (data_ptr.sub(data_ptr_start as usize) as MaxOffset)
-                    .wrapping_add(offset as MaxOffset);
+                *offsets.add(*insert_idx as usize) =
+                    data_ptr.offset_from(data_ptr_start) as MaxOffset;
 
-                *insert_entry_ptr = (*insert_entry_ptr).add(1); // advance to next entry
+                *insert_idx += 1; // advance to next entry
 
                 data_ptr = data_ptr.add(1);
             }
@@ -189,7 +385,7 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
 
         #[cfg(target_pointer_width = "64")]
         {
-            let mut data_ofs = 0;
+            let mut data_ofs = start;
             let data_len = data.len();
 
             while data_ofs < data_len.saturating_sub(16) {
@@ -200,14 +396,10 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
                 for shift in 0..7 {
                     // Successfully unrolled by LLVM
                     let key = ((chunk >> (shift * 8)) & 0xFFFF) as u16;
-                    let insert_entry_ptr = dict_insert_entry_ptrs.as_mut_ptr().add(key as usize);
-
-                    **insert_entry_ptr = ((data.as_ptr().add(data_ofs + shift) as usize
-                        - data.as_ptr() as usize)
-                        as MaxOffset)
-                        .wrapping_add(offset as MaxOffset);
+                    let insert_idx = dict_insert_indices.as_mut_ptr().add(key as usize);
 
-                    *insert_entry_ptr = (*insert_entry_ptr).add(1);
+                    *offsets.add(*insert_idx as usize) = (data_ofs + shift) as MaxOffset;
+                    *insert_idx += 1;
                 }
 
                 // Handle the 16-bit number that spans the boundary between this chunk and the next
@@ -215,12 +407,10 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
                 let next_chunk = read_unaligned(data.as_ptr().add(data_ofs + 8) as *const u64);
                 let next_chunk_byte = (next_chunk & 0xFF) << 8;
                 let key = ((chunk >> 56) | next_chunk_byte) as u16;
-                let insert_entry_ptr = dict_insert_entry_ptrs.as_mut_ptr().add(key as usize);
+                let insert_idx = dict_insert_indices.as_mut_ptr().add(key as usize);
 
-                **insert_entry_ptr = ((data.as_ptr().add(data_ofs + 7) as usize
-                    - data.as_ptr() as usize) as MaxOffset)
-                    .wrapping_add(offset as MaxOffset);
-                *insert_entry_ptr = (*insert_entry_ptr).add(1);
+                *offsets.add(*insert_idx as usize) = (data_ofs + 7) as MaxOffset;
+                *insert_idx += 1;
 
                 data_ofs += 8;
             }
@@ -228,27 +418,21 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
             // Process any remaining bytes in the data.
             while data_ofs < data_len.saturating_sub(1) {
                 let key = read_unaligned(data.as_ptr().add(data_ofs) as *const u16);
-                let insert_entry_ptr = dict_insert_entry_ptrs.as_mut_ptr().add(key as usize);
+                let insert_idx = dict_insert_indices.as_mut_ptr().add(key as usize);
 
-                **insert_entry_ptr = ((data.as_ptr().add(data_ofs) as usize
-                    - data.as_ptr() as usize) as MaxOffset)
-                    .wrapping_add(offset as MaxOffset);
-                *insert_entry_ptr = (*insert_entry_ptr).add(1);
+                *offsets.add(*insert_idx as usize) = data_ofs as MaxOffset;
+                *insert_idx += 1;
                 data_ofs += 1;
             }
         }
     }
 
-    /// Creates a frequency table for the given data.
+    /// Fills the (zeroed) frequency table with the number of occurrences of each 2 byte sequence in the given data.
     ///
     /// # Parameters
     /// - `data`: The data to create the frequency table from.
-    pub(crate) unsafe fn create_frequency_table(&self, data: &[u8]) -> Box<[FreqCountType], S> {
-        // This actually has no overhead.
-
-        let result =
-            Box::<[FreqCountType], S>::new_zeroed_slice_in(MAX_U16, self.short_lived_allocator);
-        let mut result = result.assume_init();
+    pub(crate) unsafe fn fill_frequency_table(&mut self, data: &[u8]) {
+        let result = &mut self.freq_table;
 
         #[cfg(not(target_pointer_width = "64"))]
         {
@@ -262,8 +446,6 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
                 result[index as usize] += 1;
                 data_ofs += 1;
             }
-
-            result
         }
 
         #[cfg(target_pointer_width = "64")]
@@ -295,14 +477,15 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
                 result[index as usize] += 1;
                 data_ofs += 1;
             }
-
-            result
         }
     }
 
     /// Returns a slice of offsets for the given key which are greater than or equal to `min_ofs`
     /// and less than or equal to `max_ofs`.
     ///
+    /// The returned offsets are relative to [`CompDict::window_start`], while `min_ofs` and `max_ofs`
+    /// are offsets within the whole input.
+    ///
     /// # Parameters
     ///
     /// - `key`: The key to search for.
@@ -317,30 +500,96 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
         // Ensure that the key is within the bounds of the dictionary.
         debug_assert!(key as usize <= MAX_U16, "Key is out of range!");
 
+        let min_ofs = self.to_window_offset(min_ofs);
+        let max_ofs = self.to_window_offset(max_ofs);
+        let offsets = self.offsets_ptr();
         let entry = &mut self.get_dict_mut()[key as usize];
-        let mut cur_last_read_item = entry.last_read_item;
+        let last_item = entry.last_item as usize;
+        let mut cur_last_read_item = entry.last_read_item as usize;
 
-        // Advance the 'last_read_item' pointer to the first offset greater than or equal to min_ofs
-        while cur_last_read_item < entry.last_item && *cur_last_read_item < min_ofs as MaxOffset {
-            cur_last_read_item = cur_last_read_item.add(1);
+        // Advance the 'last_read_item' index to the first offset greater than or equal to min_ofs
+        while cur_last_read_item < last_item && *offsets.add(cur_last_read_item) < min_ofs {
+            cur_last_read_item += 1;
         }
-        entry.last_read_item = cur_last_read_item;
+        entry.last_read_item = cur_last_read_item as MaxOffset;
 
         // Find the end of the range - the first offset greater than max_ofs
         // TODO: Try last read max item.
-        let mut end = entry.last_read_item_max;
-        while end < entry.last_item && *end <= max_ofs as MaxOffset {
-            end = end.add(1);
+        let mut end = entry.last_read_item_max as usize;
+        while end < last_item && *offsets.add(end) <= max_ofs {
+            end += 1;
         }
-        entry.last_read_item_max = end;
+        entry.last_read_item_max = end as MaxOffset;
 
         // Create a slice from the updated range
+        slice::from_raw_parts(offsets.add(cur_last_read_item), end - cur_last_read_item)
+    }
+
+    /// Returns a slice of all offsets for the given key, from the last read item onwards.
+    ///
+    /// # Safety
+    ///
+    /// The dictionary must be initialized.
+    #[cfg(test)]
+    pub unsafe fn get_items(&mut self, key: u16) -> &[MaxOffset] {
+        let offsets = self.offsets_ptr();
+        let entry = &self.get_dict_mut()[key as usize];
         slice::from_raw_parts(
-            cur_last_read_item,
-            end.offset_from(cur_last_read_item) as usize,
+            offsets.add(entry.last_read_item as usize),
+            (entry.last_item - entry.last_read_item) as usize,
         )
     }
 
+    /// Returns a pointer to the offsets section of [`CompDict::buf`].
+    #[inline(always)]
+    fn offsets_ptr(&self) -> *mut MaxOffset {
+        unsafe {
+            self.buf
+                .as_ptr()
+                .add(ENTRY_SECTION_LEN + DICTIONARY_PADDING) as *mut MaxOffset
+        }
+    }
+
+    /// Returns the offset (within the whole input) of the data the dictionary was last initialized with.
+    /// Add this to offsets returned by [`CompDict::get_item`] to get offsets within the whole input.
+    #[inline(always)]
+    pub fn window_start(&self) -> usize {
+        self.window_start
+    }
+
+    /// Converts an offset within the whole input to one relative to [`CompDict::window_start`],
+    /// clamped to the range of [`MaxOffset`].
+    #[inline(always)]
+    fn to_window_offset(&self, offset: usize) -> MaxOffset {
+        offset
+            .saturating_sub(self.window_start)
+            .min(MaxOffset::MAX as usize) as MaxOffset
+    }
+
+    /// Returns the maximum length of data this [`CompDict`] can be initialized with.
+    pub fn data_len(&self) -> usize {
+        (self.alloc_length - ENTRY_SECTION_LEN - DICTIONARY_PADDING) / size_of::<MaxOffset>()
+    }
+
+    /// Returns the total time spent in [`CompDict::init`] since creation, or the last call to
+    /// [`CompDict::reset_init_time`], while [`CompDict::set_collect_stats`] was enabled.
+    /// Always zero without the `std` feature.
+    pub fn init_time(&self) -> Duration {
+        self.init_time
+    }
+
+    /// Sets whether [`CompDict::init`] and [`CompDict::slide`] measure the time they take.
+    /// Disabled by default, as reading the clock isn't free.
+    #[cfg(feature = "std")]
+    pub fn set_collect_stats(&mut self, collect_stats: bool) {
+        self.collect_stats = collect_stats;
+    }
+
+    /// Resets the time returned by [`CompDict::init_time`] to zero.
+    pub fn reset_init_time(&mut self) {
+        self.init_time = Duration::ZERO;
+    }
+
     /// Retrieves the dictionary entries section of this [`CompDict`].
     pub fn get_dict_mut(&mut self) -> &mut [CompDictEntry; MAX_U16] {
         unsafe {
@@ -350,37 +599,117 @@ impl<L: Allocator + Copy, S: Allocator + Copy> CompDict<L, S> {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl CompDict {
     /// Create a new [`CompDict`] without initializing it.
     ///
     /// # Parameters
     ///
     /// - `data_len`: The length of the data that will be used to initialize the dictionary.
+    ///   At most [`MAX_DATA_LEN`].
+    ///
+    /// # Panics
+    ///
+    /// If allocation fails, or `data_len` is greater than [`MAX_DATA_LEN`].
     pub fn new(data_len: usize) -> Self {
         Self::new_in(data_len, Global, Global)
     }
 }
 
-impl CompDictEntry {
-    /// Returns a slice of offsets between `last_read_item` and `last_item`.
+impl CompDict<NoAlloc, NoAlloc> {
+    /// Returns the number of bytes [`CompDict::from_scratch`] needs for a dictionary of `data_len` bytes.
+    pub(crate) const fn scratch_size(data_len: usize) -> usize {
+        INSERT_INDICES_LEN
+            + FREQ_TABLE_LEN
+            + ENTRY_SECTION_LEN
+            + DICTIONARY_PADDING
+            + size_of::<MaxOffset>() * data_len
+    }
+
+    /// Create a new [`CompDict`] in caller-provided memory, without initializing it.
+    ///
+    /// Layout:
+    /// - [MaxOffset; MAX_U16] (insert_indices)
+    /// - [FreqCountType; MAX_U16] (freq_table)
+    /// - the same layout as [`CompDict::buf`]
+    ///
+    /// # Parameters
+    ///
+    /// - `scratch`: Memory to create the dictionary in.
+    /// - `data_len`: The length of the data that will be used to initialize the dictionary.
     ///
     /// # Safety
     ///
-    /// This function is unsafe as it operates on raw pointers.
-    /// The caller must ensure that `last_read_item` and `last_item` are valid.
-    #[cfg(test)]
-    pub unsafe fn get_items(&mut self) -> &[MaxOffset] {
-        // Calculate the length of the slice by finding the distance between the pointers.
-        let length = self.last_item.offset_from(self.last_read_item) as usize;
-
-        // Create and return a slice from the raw pointers.
-        slice::from_raw_parts(self.last_read_item, length)
+    /// `scratch` must be aligned to [`ALLOC_ALIGNMENT`], be valid for [`CompDict::scratch_size`] bytes,
+    /// and outlive the dictionary.
+    pub(crate) unsafe fn from_scratch(scratch: *mut u8, data_len: usize) -> Self {
+        let insert_indices = AllocBox::from_raw_in(scratch as *mut [MaxOffset; MAX_U16], NoAlloc);
+
+        // The frequency table is expected to be zeroed outside of init.
+        let freq_table_ptr = scratch.add(INSERT_INDICES_LEN) as *mut FreqCountType;
+        freq_table_ptr.write_bytes(0, MAX_U16);
+        let freq_table =
+            AllocBox::from_raw_in(slice_from_raw_parts_mut(freq_table_ptr, MAX_U16), NoAlloc);
+
+        CompDict {
+            buf: NonNull::new_unchecked(scratch.add(INSERT_INDICES_LEN + FREQ_TABLE_LEN)),
+            alloc_length: ENTRY_SECTION_LEN
+                + DICTIONARY_PADDING
+                + size_of::<MaxOffset>() * data_len,
+            long_lived_allocator: NoAlloc,
+            insert_indices,
+            freq_table,
+            window_start: 0,
+            window_len: 0,
+            init_time: Duration::ZERO,
+            #[cfg(feature = "std")]
+            collect_stats: false,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_prelude::LimitedAllocator;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn try_new_in_reports_allocation_failure_without_leaking() {
+        let data_len = 100;
+        let required = ENTRY_SECTION_LEN
+            + DICTIONARY_PADDING
+            + size_of::<MaxOffset>() * data_len
+            + size_of::<[MaxOffset; MAX_U16]>()
+            + size_of::<[FreqCountType; MAX_U16]>();
+
+        // Fail at each allocation in turn; whatever was allocated before must be freed.
+        for budget in [0, required / 2, required - 1] {
+            let allocator = LimitedAllocator::new(budget);
+            assert!(CompDict::try_new_in(data_len, &allocator, &allocator).is_err());
+            assert_eq!(allocator.remaining(), budget);
+        }
+
+        let allocator = LimitedAllocator::new(required);
+        let dict = CompDict::try_new_in(data_len, &allocator, &allocator).unwrap();
+        assert_eq!(allocator.remaining(), 0);
+        drop(dict);
+        assert_eq!(allocator.remaining(), required);
+    }
+
+    #[test]
+    fn try_new_in_rejects_data_longer_than_max() {
+        let allocator = LimitedAllocator::new(usize::MAX);
+        assert!(CompDict::try_new_in(MAX_DATA_LEN + 1, &allocator, &allocator).is_err());
+        assert_eq!(allocator.remaining(), usize::MAX);
+        assert!(CompDict::try_new_in(MAX_DATA_LEN, &allocator, &allocator).is_ok());
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_rejects_data_longer_than_max() {
+        CompDict::new(MAX_DATA_LEN + 1);
+    }
 
     #[test]
     fn can_create_dict() {
@@ -390,14 +719,8 @@ mod tests {
             comp_dict.init(data, 0);
 
             // Assert that the items were correctly inserted.
-            assert_eq!(
-                comp_dict.get_dict_mut()[0x4241_u16.to_le() as usize].get_items(),
-                &[0]
-            );
-            assert_eq!(
-                comp_dict.get_dict_mut()[0x4342_u16.to_le() as usize].get_items(),
-                &[1]
-            );
+            assert_eq!(comp_dict.get_items(0x4241_u16.to_le()), &[0]);
+            assert_eq!(comp_dict.get_items(0x4342_u16.to_le()), &[1]);
 
             // Ensure we can get a slice.
             let result = comp_dict.get_item(0x4141, 3, 4);
@@ -406,7 +729,7 @@ mod tests {
             // Access the next in sequence, and ensure it was correctly advanced.
             let result = comp_dict.get_item(0x4141, 4, 5);
             assert_eq!(&[4, 5], result);
-            assert_eq!(*comp_dict.get_dict_mut()[0x4141].last_read_item, 4);
+            assert_eq!(comp_dict.get_items(0x4141)[0], 4);
 
             // Access beyond end of sequence
             let result = comp_dict.get_item(0x4141, 5, 99);
@@ -414,6 +737,82 @@ mod tests {
         }
     }
 
+    /// Returns the locations of every key in the dictionary.
+    unsafe fn all_items(comp_dict: &mut CompDict) -> Vec<Vec<MaxOffset>> {
+        (0..MAX_U16)
+            .map(|key| comp_dict.get_items(key as u16).to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn slide_matches_init() {
+        // Few distinct bytes, so keys have many locations, some spanning both windows.
+        let mut seed = 0x1234_5678_u32;
+        let data: Vec<u8> = (0..100_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 28) as u8
+            })
+            .collect();
+
+        unsafe {
+            let mut sliding = CompDict::new(0x4000);
+            let mut fresh = CompDict::new(0x4000);
+            sliding.init(&data[..0x4000], 0);
+
+            // Includes steps which overlap by a single key, and no keys (falls back to init).
+            for (start, len) in [
+                (0x3000, 0x4000),
+                (0x3001, 0x3FFF),
+                (0x6FFF, 0x4000),
+                (0x8000, 0x2000),
+                (0x9FFE, 0x4000),
+                (0xE000, 0x1000),
+                (0xF000, 0x4000),
+            ] {
+                let window = &data[start..start + len];
+                sliding.slide(window, start);
+                fresh.init(window, start);
+                assert_eq!(sliding.window_start(), start);
+                assert_eq!(all_items(&mut sliding), all_items(&mut fresh), "{start:#X}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn init_time_is_only_measured_when_collecting_stats() {
+        let data: Vec<u8> = (0..0x4000).map(|x| (x % 251) as u8).collect();
+        let mut comp_dict = CompDict::new(data.len());
+
+        unsafe {
+            comp_dict.init(&data, 0);
+            comp_dict.slide(&data[0x2000..], 0x2000);
+            assert_eq!(comp_dict.init_time(), Duration::ZERO);
+
+            comp_dict.set_collect_stats(true);
+            comp_dict.init(&data, 0);
+            assert!(comp_dict.init_time() > Duration::ZERO);
+        }
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn can_create_dict_beyond_4gib() {
+        unsafe {
+            let data = &[0x41, 0x42, 0x43, 0x41, 0x41, 0x41, 0x41, 0x41, 0x41];
+            let offset = 0x1_0000_0000 - 4; // straddles u32::MAX
+            let mut comp_dict = CompDict::new(data.len());
+            comp_dict.init(data, offset);
+
+            let result = comp_dict.get_item(0x4141, offset + 3, offset + 4);
+            assert_eq!(&[3, 4], result);
+
+            let result = comp_dict.get_item(0x4141, offset + 5, offset + 99);
+            assert_eq!(&[5, 6, 7], result);
+        }
+    }
+
     #[test]
     fn can_create_dict_with_offset() {
         unsafe {
@@ -421,29 +820,24 @@ mod tests {
             let offset = 1000;
             let mut comp_dict = CompDict::new(data.len());
             comp_dict.init(data, offset);
+            assert_eq!(comp_dict.window_start(), offset);
 
-            // Assert that the items were correctly inserted with the offset.
-            assert_eq!(
-                comp_dict.get_dict_mut()[0x4241_u16.to_le() as usize].get_items(),
-                &[1000]
-            );
-            assert_eq!(
-                comp_dict.get_dict_mut()[0x4342_u16.to_le() as usize].get_items(),
-                &[1001]
-            );
+            // Items are stored relative to the window start.
+            assert_eq!(comp_dict.get_items(0x4241_u16.to_le()), &[0]);
+            assert_eq!(comp_dict.get_items(0x4342_u16.to_le()), &[1]);
 
             // Ensure we can get a slice with offsets.
             let result = comp_dict.get_item(0x4141, 1003, 1004);
-            assert_eq!(&[1003, 1004], result);
+            assert_eq!(&[3, 4], result);
 
             // Access the next in sequence, and ensure it was correctly advanced.
             let result = comp_dict.get_item(0x4141, 1004, 1005);
-            assert_eq!(&[1004, 1005], result);
-            assert_eq!(*comp_dict.get_dict_mut()[0x4141].last_read_item, 1004);
+            assert_eq!(&[4, 5], result);
+            assert_eq!(comp_dict.get_items(0x4141)[0], 4);
 
             // Access beyond end of sequence
             let result = comp_dict.get_item(0x4141, 1005, 1099);
-            assert_eq!(&[1005, 1006, 1007], result);
+            assert_eq!(&[5, 6, 7], result);
         }
     }
 }
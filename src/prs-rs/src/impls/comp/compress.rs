@@ -1,9 +1,13 @@
+use super::compressor::PrsCompressor;
+use super::hash_chain::HashChain;
 use super::lz77_matcher::{
-    lz77_get_longest_match_fast, lz77_get_longest_match_slow, Lz77Match, Lz77Parameters,
+    lz77_get_longest_match_brute_force, lz77_get_longest_match_fast, lz77_get_longest_match_slow,
+    lz77_get_longest_matches, Lz77Match, Lz77Parameters,
 };
+use super::small_hash_chain::SmallHashChain;
 use crate::impls::comp::comp_dict::CompDict;
-use crate::prelude::Allocator;
-use core::{ptr::write_unaligned, slice};
+use crate::prelude::{AllocError, Allocator};
+use core::{mem::swap, ptr::write_unaligned, slice};
 
 /// Size of a CompDict window.
 ///
@@ -22,19 +26,100 @@ use core::{ptr::write_unaligned, slice};
 /// - [MaxOffset; WINDOW_SIZE] (offsets), variable size. This buffer stores offsets of all items of 2 byte combinations.
 ///
 /// Which is:
-/// - 12/24 (CompDictEntry) * 64K = 768K/1.5M
-/// - 4 (MaxOffset) * WINDOW_SIZE = 4 * 64K = 256K
+/// - 6 (CompDictEntry) * 64K = 384K
+/// - 2 (MaxOffset) * WINDOW_SIZE = 2 * 64K = 128K
 ///
-/// During init we also use up:
-/// - 4/8 (InsertPointer) * 64K = 256K/512K
+/// The dictionary also keeps the following scratch buffers, used during init:
+/// - 2 (InsertIndex) * 64K = 128K
 /// - 2 (FreqTableEntry) * 64K = 128K
-const WINDOW_SIZE: usize = u16::MAX as usize;
+pub(crate) const WINDOW_SIZE: usize = u16::MAX as usize;
 
-const MAX_OFFSET: usize = 0x1FFF;
-const COPY_MAX_LENGTH: isize = 0x100;
-const SHORT_COPY_MAX_OFFSET: isize = 0x100;
-const SHORT_COPY_MAX_LEN: usize = 5;
-const SHORT_COPY_MIN_LEN: usize = 2;
+pub(crate) const MAX_OFFSET: usize = 0x1FFF;
+
+/// Inputs shorter than this are compressed by [`compress_range_small`] rather than [`compress_range`],
+/// when using [`CompressionLevel::Greedy`] or [`CompressionLevel::Lazy`].
+///
+/// Below this, setting up the [`CompDict`] takes longer than compressing with a [`SmallHashChain`].
+/// Also sizes [`SmallHashChain`], which lives on the stack.
+pub(crate) const SMALL_INPUT_THRESHOLD: usize = 0x1000;
+pub(crate) const COPY_MAX_LENGTH: isize = 0x100;
+pub(crate) const SHORT_COPY_MAX_OFFSET: isize = 0x100;
+pub(crate) const SHORT_COPY_MAX_LEN: usize = 5;
+pub(crate) const SHORT_COPY_MIN_LEN: usize = 2;
+pub(crate) const LONG_COPY_MIN_LEN: usize = 3;
+pub(crate) const LONG_COPY_SMALL_MAX_LEN: usize = 9;
+
+/// Max distance of a copy made by SEGA's compressor, see [`CompressionLevel::Sega`].
+const SEGA_MAX_OFFSET: usize = 0xFF;
+
+// Exact cost (in bits) of each opcode, as used by the lazy and optimal parsers.
+const LITERAL_COST: u32 = 1 + 8; // 1 control bit + 1 byte
+const SHORT_COPY_COST: u32 = 4 + 8; // 4 control bits + 1 byte
+const LONG_COPY_SMALL_COST: u32 = 2 + 16; // 2 control bits + 2 bytes
+const LONG_COPY_LARGE_COST: u32 = 2 + 24; // 2 control bits + 3 bytes
+
+/// Strategy used by the compressor to choose which matches to encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionLevel {
+    /// Encodes the longest match found at each position.
+    #[default]
+    Greedy,
+    /// Like [`CompressionLevel::Greedy`], but before encoding a match, also checks for a match at
+    /// the next position. If that match saves more space, a literal is encoded instead, and the
+    /// next match is used.
+    ///
+    /// This gets most of the gains of [`CompressionLevel::Optimal`], at a fraction of the cost.
+    Lazy,
+    /// Like [`CompressionLevel::Greedy`], but only checks the nearest
+    /// [`CompressionOptions::max_candidates`] matches at each position.
+    ///
+    /// Matches are found via a hash chain which is updated as data is compressed, rather than a
+    /// dictionary built up front, so this trades compression ratio for a lot of speed.
+    Fast,
+    /// Chooses the combination of literals and copies which gives the smallest output,
+    /// by minimising the exact bit cost of the PRS opcodes.
+    ///
+    /// This is much slower than [`CompressionLevel::Greedy`], and uses an extra
+    /// 4 bytes of memory per byte of input.
+    Optimal,
+    /// Reproduces the output of SEGA's original compressor byte for byte, so recompressing
+    /// unmodified game files gives identical data.
+    ///
+    /// Encodes the longest match within 0xFF bytes, preferring the nearest one on ties.
+    /// Because of the small window, this compresses worse than [`CompressionLevel::Greedy`].
+    Sega,
+}
+
+/// Options which control how data is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// Strategy used to choose which matches to encode.
+    pub level: CompressionLevel,
+    /// Maximum number of candidate matches checked at each position (nearest first),
+    /// when using [`CompressionLevel::Fast`]. Values below 1 are treated as 1.
+    ///
+    /// Other levels always check all candidates.
+    pub max_candidates: usize,
+}
+
+impl CompressionOptions {
+    /// Default value of [`CompressionOptions::max_candidates`].
+    pub const DEFAULT_MAX_CANDIDATES: usize = 8;
+
+    /// Creates options which compress with the given [`CompressionLevel`].
+    pub const fn new(level: CompressionLevel) -> Self {
+        Self {
+            level,
+            max_candidates: Self::DEFAULT_MAX_CANDIDATES,
+        }
+    }
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self::new(CompressionLevel::default())
+    }
+}
 
 /// Parameters
 ///
@@ -53,37 +138,234 @@ const SHORT_COPY_MIN_LEN: usize = 2;
 /// and the remaining parameters are valid.
 pub unsafe fn prs_compress<L: Allocator + Copy, S: Allocator + Copy>(
     source: *const u8,
-    mut dest: *mut u8,
+    dest: *mut u8,
     source_len: usize,
     long_lived_allocator: L,
     short_lived_allocator: S,
 ) -> usize {
+    try_prs_compress(
+        source,
+        dest,
+        source_len,
+        long_lived_allocator,
+        short_lived_allocator,
+    )
+    .unwrap()
+}
+
+/// Same as [`prs_compress`], but returns [`AllocError`] if either allocator fails to allocate,
+/// instead of panicking. Nothing is written to `destination` on failure.
+///
+/// # Safety
+///
+/// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
+/// and the remaining parameters are valid.
+pub unsafe fn try_prs_compress<L: Allocator + Copy, S: Allocator + Copy>(
+    source: *const u8,
+    dest: *mut u8,
+    source_len: usize,
+    long_lived_allocator: L,
+    short_lived_allocator: S,
+) -> Result<usize, AllocError> {
     let orig_dest = dest as usize;
+    let mut source_ofs = 0;
+
+    if source_len < SMALL_INPUT_THRESHOLD {
+        let mut writer = CompressWriter::new(dest);
+        compress_range_small::<false>(source, source_len, &mut source_ofs, &mut writer);
+        writer.write_terminator();
+        return Ok(writer.dest as usize - orig_dest);
+    }
+
+    let mut dict = CompDict::try_new_in(WINDOW_SIZE, long_lived_allocator, short_lived_allocator)?;
+    let mut writer = CompressWriter::new(dest);
+    compress_range::<false, L, S>(
+        &mut dict,
+        source,
+        source_len,
+        &mut source_ofs,
+        true,
+        &mut writer,
+    );
+    writer.write_terminator();
+    Ok(writer.dest as usize - orig_dest)
+}
+
+/// Same as [`prs_compress`], but with a custom [`CompressionLevel`].
+///
+/// # Safety
+///
+/// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
+/// and the remaining parameters are valid.
+pub unsafe fn prs_compress_with_level<L: Allocator + Copy, S: Allocator + Copy>(
+    source: *const u8,
+    dest: *mut u8,
+    source_len: usize,
+    level: CompressionLevel,
+    long_lived_allocator: L,
+    short_lived_allocator: S,
+) -> usize {
+    prs_compress_with_options(
+        source,
+        dest,
+        source_len,
+        &CompressionOptions::new(level),
+        long_lived_allocator,
+        short_lived_allocator,
+    )
+}
+
+/// Same as [`prs_compress`], but with custom [`CompressionOptions`].
+///
+/// # Safety
+///
+/// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
+/// and the remaining parameters are valid.
+pub unsafe fn prs_compress_with_options<L: Allocator + Copy, S: Allocator + Copy>(
+    source: *const u8,
+    dest: *mut u8,
+    source_len: usize,
+    options: &CompressionOptions,
+    long_lived_allocator: L,
+    short_lived_allocator: S,
+) -> usize {
+    PrsCompressor::new_in(*options, long_lived_allocator, short_lived_allocator)
+        .compress_unsafe(source, source_len, dest)
+}
+
+/// Current output state of the compressor.
+pub(crate) struct CompressWriter {
+    /// Where the next byte of compressed data will be written.
+    pub(crate) dest: *mut u8,
+    /// The control byte currently being filled.
+    pub(crate) control_byte_ptr: *mut u8,
+    /// The current bit position in the control byte.
+    pub(crate) control_bit_position: usize,
+}
+
+impl CompressWriter {
+    /// Starts writing a new PRS stream at `dest`.
+    ///
+    /// # Safety
+    ///
+    /// `dest` must be valid for writes.
+    #[inline(always)]
+    pub(crate) unsafe fn new(mut dest: *mut u8) -> Self {
+        let control_byte_ptr = reserve_control_byte(&mut dest);
+        Self {
+            dest,
+            control_byte_ptr,
+            control_bit_position: 0,
+        }
+    }
 
-    // Write first control byte.
+    /// Writes the `01` opcode with offset 0 that marks the end of the PRS stream.
+    #[inline(always)]
+    pub(crate) unsafe fn write_terminator(&mut self) {
+        append_control_bit(
+            0,
+            &mut self.dest,
+            &mut self.control_bit_position,
+            &mut self.control_byte_ptr,
+        );
+        append_control_bit(
+            1,
+            &mut self.dest,
+            &mut self.control_bit_position,
+            &mut self.control_byte_ptr,
+        );
+
+        append_byte(0x00, &mut self.dest);
+        append_byte(0x00, &mut self.dest);
+    }
+
+    /// Appends a single control bit, reserving a new control byte if the current one is full.
+    #[inline(always)]
+    #[cfg(feature = "alloc")]
+    pub(crate) unsafe fn write_control_bit(&mut self, bit: u8) {
+        append_control_bit(
+            bit,
+            &mut self.dest,
+            &mut self.control_bit_position,
+            &mut self.control_byte_ptr,
+        );
+    }
+
+    /// Appends a single data byte.
+    #[inline(always)]
+    #[cfg(feature = "alloc")]
+    pub(crate) unsafe fn write_byte(&mut self, value: u8) {
+        append_byte(value, &mut self.dest);
+    }
+}
+
+/// Indexes the window of data at `window_start` in `dict`.
+///
+/// The first window of each compressed range is indexed from scratch, as the dictionary may hold
+/// a previous input. Later windows overlap the previous one, so the dictionary is slid forward instead.
+#[inline(always)]
+unsafe fn index_window<L: Allocator + Copy, S: Allocator + Copy>(
+    dict: &mut CompDict<L, S>,
+    window: &[u8],
+    window_start: usize,
+    initialized: &mut bool,
+) {
+    if *initialized {
+        dict.slide(window, window_start);
+    } else {
+        dict.init(window, window_start);
+        *initialized = true;
+    }
+}
+
+/// Compresses the data in `source` starting at `source_ofs`, with any data before `source_ofs` being
+/// used as look-behind (up to `MAX_OFFSET` bytes).
+///
+/// # Parameters
+///
+/// - `dict`: The dictionary to use, allocated with at least `WINDOW_SIZE` entries.
+/// - `source`: A pointer to the decompressed data.
+/// - `source_len`: Length of the decompressed data.
+/// - `source_ofs`: Offset of the first byte to compress, advanced as data is compressed.
+/// - `is_final`: If true, compresses all data until `source_len`. Otherwise stops as soon as
+///   there are less than `COPY_MAX_LENGTH` bytes left, as more data may follow.
+/// - `writer`: Where the compressed data is written to.
+///
+/// If `LAZY` is true, uses lazy matching ([`CompressionLevel::Lazy`]), otherwise greedy matching.
+///
+/// # Safety
+///
+/// `source` must be valid for `source_len` bytes, and `writer` must have sufficient space
+/// for the compressed data (max length: [`crate::util::prs_calculate_max_compressed_size`]).
+#[inline(always)]
+pub(crate) unsafe fn compress_range<const LAZY: bool, L: Allocator + Copy, S: Allocator + Copy>(
+    dict: &mut CompDict<L, S>,
+    source: *const u8,
+    source_len: usize,
+    source_ofs: &mut usize,
+    is_final: bool,
+    writer: &mut CompressWriter,
+) {
     let mut last_init_covered_all = false;
-    let mut control_byte_ptr = reserve_control_byte(&mut dest);
-    let mut control_bit_position = 0;
-    let mut source_ofs = 0;
-    let mut dict = CompDict::new_in(WINDOW_SIZE, long_lived_allocator, short_lived_allocator);
+    let mut dict_initialized = false;
 
     // First byte is always a direct encode, so we can encode it before looping,
     // doing this here saves a branch in lz77_get_longest_match, improving perf.
-    if source_len > 0 {
+    if *source_ofs == 0 && source_len > 0 {
         append_control_bit(
             1,
-            &mut dest,
-            &mut control_bit_position,
-            &mut control_byte_ptr,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
         );
-        append_byte(*source, &mut dest);
-        source_ofs += 1;
+        append_byte(*source, &mut writer.dest);
+        *source_ofs += 1;
     }
 
     // Loop through all the bytes, as long as there are less than COPY_MAX_LENGTH bytes left.
     // We eliminate a branch inside lz77_get_longest_match by doing this, saving a bit of perf.
     let fast_processing_end = source_len.saturating_sub(COPY_MAX_LENGTH as usize);
-    while source_ofs < fast_processing_end {
+    while *source_ofs < fast_processing_end {
         let window_start = source_ofs.saturating_sub(MAX_OFFSET);
         let window_end = window_start + WINDOW_SIZE;
         let window_end = if window_end >= source_len {
@@ -94,23 +376,20 @@ pub unsafe fn prs_compress<L: Allocator + Copy, S: Allocator + Copy>(
         };
         let window_slice =
             slice::from_raw_parts(source.add(window_start), window_end - window_start);
-        dict.init(window_slice, window_start);
+        index_window(dict, window_slice, window_start, &mut dict_initialized);
 
         // Process the current window.
-        while source_ofs < window_end.min(fast_processing_end) {
-            let result = lz77_get_longest_match_fast::<CompressParameters, L, S>(
-                &mut dict, source, source_ofs,
-            );
-
-            encode_lz77_match(
-                result,
-                &mut dest,
-                &mut control_bit_position,
-                &mut control_byte_ptr,
-                &mut source_ofs,
-                source,
-            );
-        }
+        encode_matches::<LAZY>(
+            |ofs| lz77_get_longest_match_fast::<CompressParameters, L, S>(dict, source, ofs),
+            window_end.min(fast_processing_end),
+            writer,
+            source_ofs,
+            source,
+        );
+    }
+
+    if !is_final {
+        return;
     }
 
     // Handle the remaining bytes.
@@ -121,53 +400,402 @@ pub unsafe fn prs_compress<L: Allocator + Copy, S: Allocator + Copy>(
         let window_start = source_ofs.saturating_sub(MAX_OFFSET);
         let window_slice =
             slice::from_raw_parts(source.add(window_start), source_len - window_start);
-        dict.init(window_slice, window_start);
+        index_window(dict, window_slice, window_start, &mut dict_initialized);
+    }
+
+    encode_matches::<LAZY>(
+        |ofs| {
+            lz77_get_longest_match_slow::<CompressParameters, L, S>(dict, source, source_len, ofs)
+        },
+        source_len.saturating_sub(1),
+        writer,
+        source_ofs,
+        source,
+    );
+
+    // There is potentially one last remaining byte.
+    if *source_ofs == source_len.wrapping_sub(1) {
+        append_control_bit(
+            1,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+        );
+        append_byte(*source.add(*source_ofs), &mut writer.dest);
+        *source_ofs += 1;
     }
+}
 
-    while source_ofs < source_len.saturating_sub(1) {
-        let result = lz77_get_longest_match_slow::<CompressParameters, L, S>(
-            &mut dict, source, source_len, source_ofs,
+/// Hash chain counterpart of [`compress_range`], for [`CompressionLevel::Fast`].
+/// Always compresses all data until `source_len`.
+///
+/// # Parameters
+///
+/// - `chain`: The hash chain to use. Must be empty if `source_ofs` is 0, otherwise must
+///   contain all positions before `source_ofs`.
+/// - `max_candidates`: Maximum number of candidate matches to check at each position.
+///
+/// Other parameters are the same as [`compress_range`].
+///
+/// # Safety
+///
+/// Same as [`compress_range`].
+pub(crate) unsafe fn compress_range_fast<L: Allocator + Copy>(
+    chain: &mut HashChain<L>,
+    source: *const u8,
+    source_len: usize,
+    source_ofs: &mut usize,
+    max_candidates: usize,
+    writer: &mut CompressWriter,
+) {
+    // We sub 1 because the hash chain reads the next 2 bytes.
+    let end = source_len.saturating_sub(1);
+    while *source_ofs < end {
+        let start = *source_ofs;
+        let result = chain.find_longest_match::<CompressParameters>(
+            source,
+            source_len,
+            start,
+            max_candidates,
         );
 
         encode_lz77_match(
             result,
-            &mut dest,
-            &mut control_bit_position,
-            &mut control_byte_ptr,
-            &mut source_ofs,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+            source_ofs,
             source,
         );
+
+        for x in start..(*source_ofs).min(end) {
+            chain.insert(source, x);
+        }
     }
 
     // There is potentially one last remaining byte.
-    if source_ofs == source_len.wrapping_sub(1) {
+    if *source_ofs == source_len.wrapping_sub(1) {
         append_control_bit(
             1,
-            &mut dest,
-            &mut control_bit_position,
-            &mut control_byte_ptr,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
         );
-        append_byte(*source.add(source_ofs), &mut dest);
+        append_byte(*source.add(*source_ofs), &mut writer.dest);
+        *source_ofs += 1;
     }
+}
 
-    // Finish the PRS file
-    append_control_bit(
-        0,
-        &mut dest,
-        &mut control_bit_position,
-        &mut control_byte_ptr,
+/// Brute force counterpart of [`compress_range`], for inputs shorter than [`SMALL_INPUT_THRESHOLD`].
+/// Always compresses all data until `source_len`.
+///
+/// Setting up a [`CompDict`] means clearing tables with an entry for every possible key, which
+/// costs more than compressing a small input. Instead, matches are found with a [`SmallHashChain`]
+/// on the stack, so the cost depends only on the size of the input. The same matches are found as
+/// with [`compress_range`], so the output is identical.
+///
+/// # Parameters
+///
+/// Same as [`compress_range`].
+///
+/// # Safety
+///
+/// Same as [`compress_range`].
+pub(crate) unsafe fn compress_range_small<const LAZY: bool>(
+    source: *const u8,
+    source_len: usize,
+    source_ofs: &mut usize,
+    writer: &mut CompressWriter,
+) {
+    debug_assert!(source_len < SMALL_INPUT_THRESHOLD);
+    let mut chain = SmallHashChain::new();
+    let mut num_inserted = 0;
+    let mut find_match = |ofs| {
+        // Matches may start at any position before `ofs`.
+        while num_inserted < ofs {
+            chain.insert(source, num_inserted);
+            num_inserted += 1;
+        }
+
+        chain.find_longest_match::<CompressParameters>(source, source_len, ofs)
+    };
+
+    // Stop at the same offsets as `compress_range`, as lazy matching never looks past them.
+    encode_matches::<LAZY>(
+        &mut find_match,
+        source_len.saturating_sub(COPY_MAX_LENGTH as usize),
+        writer,
+        source_ofs,
+        source,
     );
-    append_control_bit(
-        1,
-        &mut dest,
-        &mut control_bit_position,
-        &mut control_byte_ptr,
+    encode_matches::<LAZY>(
+        &mut find_match,
+        source_len.saturating_sub(1),
+        writer,
+        source_ofs,
+        source,
     );
 
-    append_byte(0x00, &mut dest);
-    append_byte(0x00, &mut dest);
+    // There is potentially one last remaining byte.
+    if *source_ofs == source_len.wrapping_sub(1) {
+        append_control_bit(
+            1,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+        );
+        append_byte(*source.add(*source_ofs), &mut writer.dest);
+        *source_ofs += 1;
+    }
+}
+
+/// Compresses the data in `source` starting at `source_ofs` the same way SEGA's compressor does,
+/// with any data before `source_ofs` being used as look-behind. See [`CompressionLevel::Sega`].
+///
+/// Always compresses all data until `source_len`.
+///
+/// # Parameters
+///
+/// Same as [`compress_range`].
+///
+/// # Safety
+///
+/// Same as [`compress_range`].
+pub(crate) unsafe fn compress_range_sega(
+    source: *const u8,
+    source_len: usize,
+    source_ofs: &mut usize,
+    writer: &mut CompressWriter,
+) {
+    while *source_ofs < source_len {
+        let result =
+            lz77_get_longest_match_brute_force::<SegaParameters>(source, source_len, *source_ofs);
+
+        encode_lz77_match(
+            result,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+            source_ofs,
+            source,
+        );
+    }
+}
+
+/// Finds and encodes matches, until `source_ofs` reaches `end`.
+///
+/// # Parameters
+///
+/// - `find_match`: Returns the longest match at the given offset. Must be called with increasing offsets.
+/// - `end`: Offset at which to stop. `find_match` is never called at or beyond this offset.
+///
+/// If `LAZY` is true, before encoding a match, the match at the next offset is also checked.
+/// If it saves more bits, a literal is encoded instead, and the next match is used.
+#[inline(always)]
+unsafe fn encode_matches<const LAZY: bool>(
+    mut find_match: impl FnMut(usize) -> Lz77Match,
+    end: usize,
+    writer: &mut CompressWriter,
+    source_ofs: &mut usize,
+    source: *const u8,
+) {
+    let mut next_match = None;
+    while *source_ofs < end {
+        let mut result = match next_match.take() {
+            Some(result) => result,
+            None => find_match(*source_ofs),
+        };
+
+        if LAZY && *source_ofs + 1 < end {
+            let savings = match_savings(&result);
+            if savings > 0 {
+                let next = find_match(*source_ofs + 1);
+                if match_savings(&next) > savings {
+                    result = Lz77Match {
+                        offset: 0,
+                        length: 1,
+                    };
+                    next_match = Some(next);
+                }
+            }
+        }
+
+        encode_lz77_match(
+            result,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+            source_ofs,
+            source,
+        );
+    }
+}
+
+/// Number of bits saved by encoding `result` as [`encode_lz77_match`] would,
+/// compared to encoding the same bytes as literals.
+#[inline(always)]
+fn match_savings(result: &Lz77Match) -> i32 {
+    let cost = if result.offset >= -SHORT_COPY_MAX_OFFSET
+        && result.length >= SHORT_COPY_MIN_LEN
+        && result.length <= SHORT_COPY_MAX_LEN
+    {
+        SHORT_COPY_COST
+    } else if result.length < LONG_COPY_MIN_LEN {
+        return 0; // encoded as literal
+    } else if result.length <= LONG_COPY_SMALL_MAX_LEN {
+        LONG_COPY_SMALL_COST
+    } else {
+        LONG_COPY_LARGE_COST
+    };
+
+    (result.length as u32 * LITERAL_COST) as i32 - cost as i32
+}
 
-    dest as usize - orig_dest
+/// Cheapest known way to reach a position in the optimal parser.
+#[derive(Clone, Copy)]
+pub(crate) struct ParseStep {
+    /// Length of the opcode which ends at this position, 1 for a literal.
+    length: u16,
+    /// Distance (positive) of the copy which ends at this position, 0 for a literal.
+    distance: u16,
+}
+
+/// Optimal parsing counterpart of [`compress_range`], for [`CompressionLevel::Optimal`].
+/// Always compresses all data until `source_len`.
+///
+/// `steps` is scratch space, and must have at least `source_len - source_ofs + 1` elements.
+///
+/// This walks the data forwards, finding the longest match (and longest match within `SHORT_COPY_MAX_OFFSET`)
+/// at every position, and records the cheapest way to reach each position using a literal or a copy of any
+/// length up to those. Then the cheapest path is traced back from the end and encoded.
+///
+/// # Safety
+///
+/// Same as [`compress_range`].
+pub(crate) unsafe fn compress_range_optimal<L: Allocator + Copy, S: Allocator + Copy>(
+    dict: &mut CompDict<L, S>,
+    source: *const u8,
+    source_len: usize,
+    source_ofs: &mut usize,
+    steps: &mut [ParseStep],
+    writer: &mut CompressWriter,
+) {
+    // First byte is always a direct encode, as there is nothing to match against.
+    if *source_ofs == 0 && source_len > 0 {
+        append_control_bit(
+            1,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+        );
+        append_byte(*source, &mut writer.dest);
+        *source_ofs += 1;
+    }
+
+    if *source_ofs >= source_len {
+        return;
+    }
+
+    // `steps[x]` is the last opcode of the cheapest path to `parse_start + x`.
+    let parse_start = *source_ofs;
+    let parse_len = source_len - parse_start;
+    let steps = &mut steps[..=parse_len];
+
+    // Cost (in bits) of the cheapest path to each of the next `COPY_MAX_LENGTH` positions.
+    // Indexed by position modulo `COST_RING_SIZE`.
+    const COST_RING_SIZE: usize = COPY_MAX_LENGTH as usize + 1;
+    let mut costs = [u64::MAX; COST_RING_SIZE];
+    costs[0] = 0;
+
+    let mut window_end = parse_start;
+    let mut dict_initialized = false;
+    for x in 0..parse_len {
+        let pos = parse_start + x;
+        if pos == window_end {
+            let window_start = pos.saturating_sub(MAX_OFFSET);
+            window_end = (window_start + WINDOW_SIZE).min(source_len);
+            let window_slice =
+                slice::from_raw_parts(source.add(window_start), window_end - window_start);
+            index_window(dict, window_slice, window_start, &mut dict_initialized);
+        }
+
+        let cost = costs[x % COST_RING_SIZE];
+        costs[x % COST_RING_SIZE] = u64::MAX;
+        let mut relax = |length: usize, distance: usize, opcode_cost: u32| {
+            let next_cost = cost + opcode_cost as u64;
+            let next = &mut costs[(x + length) % COST_RING_SIZE];
+            if next_cost < *next {
+                *next = next_cost;
+                steps[x + length] = ParseStep {
+                    length: length as u16,
+                    distance: distance as u16,
+                };
+            }
+        };
+
+        relax(1, 0, LITERAL_COST);
+
+        // We sub 1 because `lz77_get_longest_matches` reads the next 2 bytes.
+        if pos >= source_len - 1 {
+            continue;
+        }
+
+        let (long, short) = lz77_get_longest_matches::<CompressParameters, L, S>(
+            dict,
+            source,
+            source_len,
+            pos,
+            SHORT_COPY_MAX_LEN,
+        );
+
+        let short_distance = short.offset.unsigned_abs();
+        for length in SHORT_COPY_MIN_LEN..=short.length.min(SHORT_COPY_MAX_LEN) {
+            relax(length, short_distance, SHORT_COPY_COST);
+        }
+
+        let long_distance = long.offset.unsigned_abs();
+        for length in LONG_COPY_MIN_LEN..=long.length {
+            let opcode_cost = if length <= LONG_COPY_SMALL_MAX_LEN {
+                LONG_COPY_SMALL_COST
+            } else {
+                LONG_COPY_LARGE_COST
+            };
+
+            relax(length, long_distance, opcode_cost);
+        }
+    }
+
+    // Trace the cheapest path back from the end, turning each step into the
+    // opcode which starts (rather than ends) at its position.
+    let mut x = parse_len;
+    let mut next = ParseStep {
+        length: 0,
+        distance: 0,
+    };
+    while x > 0 {
+        swap(&mut steps[x], &mut next);
+        x -= next.length as usize;
+    }
+    steps[0] = next;
+
+    // Encode the chosen path.
+    while *source_ofs < source_len {
+        let step = steps[*source_ofs - parse_start];
+        let result = Lz77Match {
+            offset: -(step.distance as isize),
+            length: step.length as usize,
+        };
+
+        // Literals are encoded with length 1, which encode_lz77_match always encodes as a direct byte.
+        encode_lz77_match(
+            result,
+            &mut writer.dest,
+            &mut writer.control_bit_position,
+            &mut writer.control_byte_ptr,
+            source_ofs,
+            source,
+        );
+    }
 }
 
 #[inline(always)]
@@ -205,7 +833,7 @@ unsafe fn encode_lz77_match(
 
 /// Writes a short copy (00 opcode), size 2-5, offset 1-256
 #[inline(always)]
-unsafe fn write_short_copy(
+pub(crate) unsafe fn write_short_copy(
     dest: &mut *mut u8,
     result: &Lz77Match,
     control_bit_position: &mut usize,
@@ -238,7 +866,7 @@ unsafe fn write_short_copy(
 
 /// Writes a long copy (01 opcode), with small length. size 3-9, offset 1-8191
 #[inline(always)]
-unsafe fn write_long_copy_small(
+pub(crate) unsafe fn write_long_copy_small(
     dest: &mut *mut u8,
     result: &Lz77Match,
     control_bit_position: &mut usize,
@@ -259,7 +887,7 @@ unsafe fn write_long_copy_small(
 
 /// Writes a long copy (01 opcode), with large length. size 1-256, offset 1-8191
 #[inline(always)]
-unsafe fn write_long_copy_large(
+pub(crate) unsafe fn write_long_copy_large(
     dest: &mut *mut u8,
     result: &Lz77Match,
     control_bit_position: &mut usize,
@@ -333,4 +961,12 @@ struct CompressParameters;
 impl Lz77Parameters for CompressParameters {
     const MAX_OFFSET: usize = MAX_OFFSET;
     const MAX_LENGTH: usize = COPY_MAX_LENGTH as usize;
+    const MAX_SHORT_OFFSET: usize = SHORT_COPY_MAX_OFFSET as usize;
+}
+
+struct SegaParameters;
+impl Lz77Parameters for SegaParameters {
+    const MAX_OFFSET: usize = SEGA_MAX_OFFSET;
+    const MAX_LENGTH: usize = COPY_MAX_LENGTH as usize;
+    const MAX_SHORT_OFFSET: usize = SHORT_COPY_MAX_OFFSET as usize;
 }
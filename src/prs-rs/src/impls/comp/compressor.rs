@@ -0,0 +1,761 @@
+use super::alloc_box::AllocBox;
+use super::comp_dict::{CompDict, ALLOC_ALIGNMENT};
+use super::compress::{
+    compress_range, compress_range_fast, compress_range_optimal, compress_range_sega,
+    compress_range_small, CompressWriter, CompressionLevel, CompressionOptions, ParseStep,
+    MAX_OFFSET, SMALL_INPUT_THRESHOLD, WINDOW_SIZE,
+};
+use super::hash_chain::HashChain;
+use super::scratch::NoAlloc;
+#[cfg(feature = "alloc")]
+use super::stats::CompressionStats;
+#[cfg(feature = "alloc")]
+use crate::error::PrsError;
+#[cfg(feature = "alloc")]
+use crate::prelude::Global;
+use crate::prelude::{AllocError, Allocator};
+#[cfg(feature = "alloc")]
+use crate::util::prs_calculate_max_compressed_size;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::mem::{align_of, size_of};
+#[cfg(feature = "alloc")]
+use core::mem::{take, MaybeUninit};
+use core::ptr::slice_from_raw_parts_mut;
+#[cfg(feature = "alloc")]
+use core::time::Duration;
+
+/// Reusable PRS compressor.
+///
+/// Owns the dictionary and scratch buffers used during compression, so they can be reused
+/// across calls. This avoids allocating (and initializing) megabytes of memory for every
+/// compressed file, which matters when compressing many small files.
+///
+/// Buffers are allocated on first use, and only grow when a larger input needs it.
+///
+/// # Example
+///
+/// ```
+/// use prs_rs::comp::PrsCompressor;
+/// use prs_rs::decomp::prs_decompress_to_vec;
+///
+/// let mut compressor = PrsCompressor::new();
+/// for data in [&b"first file"[..], &b"second file"[..]] {
+///     let compressed = compressor.compress_to_vec(data);
+///     assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), data);
+/// }
+/// ```
+pub struct PrsCompressor<
+    #[cfg(feature = "alloc")] L: Allocator + Copy = Global,
+    #[cfg(feature = "alloc")] S: Allocator + Copy = Global,
+    #[cfg(not(feature = "alloc"))] L: Allocator + Copy,
+    #[cfg(not(feature = "alloc"))] S: Allocator + Copy,
+> {
+    options: CompressionOptions,
+    /// Dictionary used by all levels except [`CompressionLevel::Fast`] and [`CompressionLevel::Sega`].
+    /// Greedy and lazy compression only use it for inputs of at least [`SMALL_INPUT_THRESHOLD`] bytes.
+    dict: Option<CompDict<L, S>>,
+    /// Hash chain used by [`CompressionLevel::Fast`].
+    chain: Option<HashChain<L>>,
+    /// Scratch space for [`CompressionLevel::Optimal`].
+    steps: Option<AllocBox<[ParseStep], S>>,
+    /// Temporary output, for when the destination might be too small.
+    /// Not needed when compressing with scratch memory, so it doesn't require `alloc`.
+    #[cfg(feature = "alloc")]
+    output: Vec<u8>,
+    /// Temporary input, for joining the dictionary and source.
+    #[cfg(feature = "alloc")]
+    input: Vec<u8>,
+    /// Whether the dictionary measures its init time, see [`PrsCompressor::compress_with_stats`].
+    #[cfg(feature = "std")]
+    collect_stats: bool,
+    long_lived_allocator: L,
+    short_lived_allocator: S,
+}
+
+#[cfg(feature = "alloc")]
+impl PrsCompressor {
+    /// Creates a new compressor with the default [`CompressionOptions`].
+    pub fn new() -> Self {
+        Self::with_options(CompressionOptions::default())
+    }
+
+    /// Creates a new compressor with the given [`CompressionOptions`].
+    pub fn with_options(options: CompressionOptions) -> Self {
+        Self::new_in(options, Global, Global)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for PrsCompressor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrsCompressor<NoAlloc, NoAlloc> {
+    /// Returns the number of bytes of scratch memory [`PrsCompressor::from_scratch`] needs.
+    /// Includes padding, so the scratch memory may start at any address.
+    pub(crate) fn scratch_size(src_len: usize, options: &CompressionOptions) -> usize {
+        let dict_len = CompDict::scratch_size(src_len.min(WINDOW_SIZE));
+        match options.level {
+            CompressionLevel::Greedy | CompressionLevel::Lazy
+                if src_len < SMALL_INPUT_THRESHOLD =>
+            {
+                0
+            }
+            CompressionLevel::Greedy | CompressionLevel::Lazy => ALLOC_ALIGNMENT - 1 + dict_len,
+            CompressionLevel::Optimal => {
+                ALLOC_ALIGNMENT - 1 + dict_len + size_of::<ParseStep>() * (src_len + 1)
+            }
+            CompressionLevel::Fast => align_of::<usize>() - 1 + HashChain::SCRATCH_SIZE,
+            CompressionLevel::Sega => 0,
+        }
+    }
+
+    /// Creates a compressor for up to `src_len` bytes of data, whose buffers are placed in `scratch`
+    /// rather than allocated, so it never allocates while compressing.
+    ///
+    /// # Safety
+    ///
+    /// `scratch` must be at least [`PrsCompressor::scratch_size`] bytes long, and outlive the compressor.
+    pub(crate) unsafe fn from_scratch(
+        options: CompressionOptions,
+        src_len: usize,
+        scratch: &mut [u8],
+    ) -> Self {
+        let mut compressor = Self::new_in(options, NoAlloc, NoAlloc);
+        let scratch = scratch.as_mut_ptr();
+        match options.level {
+            CompressionLevel::Greedy | CompressionLevel::Lazy
+                if src_len < SMALL_INPUT_THRESHOLD => {}
+            CompressionLevel::Greedy | CompressionLevel::Lazy | CompressionLevel::Optimal => {
+                let dict_ptr = scratch.add(scratch.align_offset(ALLOC_ALIGNMENT));
+                let data_len = src_len.min(WINDOW_SIZE);
+                compressor.dict = Some(CompDict::from_scratch(dict_ptr, data_len));
+
+                if options.level == CompressionLevel::Optimal {
+                    let steps_ptr =
+                        dict_ptr.add(CompDict::scratch_size(data_len)) as *mut ParseStep;
+                    compressor.steps = Some(AllocBox::from_raw_in(
+                        slice_from_raw_parts_mut(steps_ptr, src_len + 1),
+                        NoAlloc,
+                    ));
+                }
+            }
+            CompressionLevel::Fast => {
+                let chain_ptr = scratch.add(scratch.align_offset(align_of::<usize>()));
+                compressor.chain = Some(HashChain::from_scratch(chain_ptr));
+            }
+            CompressionLevel::Sega => {}
+        }
+
+        compressor
+    }
+}
+
+impl<L: Allocator + Copy, S: Allocator + Copy> PrsCompressor<L, S> {
+    /// Creates a new compressor with the given [`CompressionOptions`].
+    /// Uses a custom allocator for short and long lived memory.
+    ///
+    /// # Parameters
+    ///
+    /// - `options`: Options which control how data is compressed.
+    /// - `long_lived_allocator`: The allocator to use for long-lived memory allocation.
+    /// - `short_lived_allocator`: The allocator to use for short-lived memory allocation.
+    pub fn new_in(
+        options: CompressionOptions,
+        long_lived_allocator: L,
+        short_lived_allocator: S,
+    ) -> Self {
+        Self {
+            options,
+            dict: None,
+            chain: None,
+            steps: None,
+            #[cfg(feature = "alloc")]
+            output: Vec::new(),
+            #[cfg(feature = "alloc")]
+            input: Vec::new(),
+            #[cfg(feature = "std")]
+            collect_stats: false,
+            long_lived_allocator,
+            short_lived_allocator,
+        }
+    }
+
+    /// Returns the options used to compress data.
+    pub fn options(&self) -> &CompressionOptions {
+        &self.options
+    }
+
+    /// Sets the options used to compress data.
+    pub fn set_options(&mut self, options: CompressionOptions) {
+        self.options = options;
+    }
+
+    /// Compresses the given data in `src`, placing it in `dest`.
+    ///
+    /// # Parameters
+    ///
+    /// - `src`: The data to compress.
+    /// - `dest`: The buffer to write the compressed data to.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes written to `dest`, or [`PrsError::BufferTooSmall`] if the compressed
+    /// data does not fit. Nothing is written to `dest` on failure.
+    ///
+    /// # Remarks
+    ///
+    /// If `dest` is at least [`prs_calculate_max_compressed_size`] bytes long, the data is compressed
+    /// directly into `dest`. Otherwise it is compressed into a buffer owned by the compressor first,
+    /// and copied over if it fits.
+    #[cfg(feature = "alloc")]
+    pub fn compress(
+        &mut self,
+        src: &[u8],
+        dest: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, PrsError> {
+        self.compress_from(src, 0, dest)
+    }
+
+    /// Compresses the given data in `src`, placing it in `dest`, allowing copies to reference
+    /// data in `dictionary`. The data must be decompressed with the same dictionary,
+    /// see [`crate::decomp::prs_decompress_with_dictionary`].
+    ///
+    /// # Parameters
+    ///
+    /// - `dictionary`: Data which is treated as if it came directly before `src`.
+    ///   Only the last 0x1FFF bytes (the max distance of a copy) are used.
+    /// - `src`: The data to compress.
+    /// - `dest`: The buffer to write the compressed data to.
+    ///
+    /// # Returns
+    ///
+    /// Same as [`PrsCompressor::compress`].
+    ///
+    /// # Remarks
+    ///
+    /// The dictionary and `src` are joined in a buffer owned by the compressor (reused between calls),
+    /// and compressed in a single pass, so copies may span the end of the dictionary.
+    #[cfg(feature = "alloc")]
+    pub fn compress_with_dictionary(
+        &mut self,
+        dictionary: &[u8],
+        src: &[u8],
+        dest: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, PrsError> {
+        // The compressor needs the dictionary and source to be contiguous.
+        let dictionary = &dictionary[dictionary.len().saturating_sub(MAX_OFFSET)..];
+        let mut input = take(&mut self.input);
+        input.clear();
+        input.extend_from_slice(dictionary);
+        input.extend_from_slice(src);
+
+        let result = self.compress_from(&input, dictionary.len(), dest);
+        self.input = input;
+        result
+    }
+
+    /// Compresses `data`, starting at `start`, with the data before it used as look-behind.
+    /// See [`PrsCompressor::compress`].
+    #[cfg(feature = "alloc")]
+    fn compress_from(
+        &mut self,
+        data: &[u8],
+        start: usize,
+        dest: &mut [MaybeUninit<u8>],
+    ) -> Result<usize, PrsError> {
+        let max_len = prs_calculate_max_compressed_size(data.len() - start);
+        if dest.len() >= max_len {
+            return Ok(unsafe {
+                self.compress_range_unsafe(
+                    data.as_ptr(),
+                    data.len(),
+                    start,
+                    dest.as_mut_ptr() as *mut u8,
+                )
+            });
+        }
+
+        let mut output = take(&mut self.output);
+        output.clear();
+        output.reserve(max_len);
+        unsafe {
+            let compressed_len =
+                self.compress_range_unsafe(data.as_ptr(), data.len(), start, output.as_mut_ptr());
+            output.set_len(compressed_len);
+        }
+
+        let result = if output.len() > dest.len() {
+            Err(PrsError::BufferTooSmall {
+                required: output.len(),
+            })
+        } else {
+            for (dst, &byte) in dest.iter_mut().zip(output.iter()) {
+                dst.write(byte);
+            }
+
+            Ok(output.len())
+        };
+
+        self.output = output;
+        result
+    }
+
+    /// Compresses the given data in `src`, returning the compressed data in a new [`Vec`].
+    #[cfg(feature = "alloc")]
+    pub fn compress_to_vec(&mut self, src: &[u8]) -> Vec<u8> {
+        let mut dest = Vec::with_capacity(prs_calculate_max_compressed_size(src.len()));
+        unsafe {
+            let compressed_len = self.compress_unsafe(src.as_ptr(), src.len(), dest.as_mut_ptr());
+            dest.set_len(compressed_len);
+        }
+
+        dest
+    }
+
+    /// Compresses the given data in `src`, returning the compressed data in a new [`Vec`],
+    /// along with statistics about the compressed data and the time spent compressing it.
+    ///
+    /// # Remarks
+    ///
+    /// The opcode statistics are collected from the compressed output, so match exactly what
+    /// was written.
+    #[cfg(feature = "alloc")]
+    pub fn compress_with_stats(&mut self, src: &[u8]) -> (Vec<u8>, CompressionStats) {
+        if let Some(dict) = self.dict.as_mut() {
+            dict.reset_init_time();
+        }
+
+        #[cfg(feature = "std")]
+        let start_time = {
+            self.collect_stats = true;
+            std::time::Instant::now()
+        };
+        let compressed = self.compress_to_vec(src);
+        #[cfg(feature = "std")]
+        let total_time = {
+            self.collect_stats = false;
+            start_time.elapsed()
+        };
+
+        let mut stats = CompressionStats::from_compressed(&compressed)
+            .expect("compressor output is a valid PRS stream");
+
+        // Levels which don't use the dictionary leave it untouched, so its time is still zero.
+        stats.dict_init_time = self
+            .dict
+            .as_ref()
+            .map_or(Duration::ZERO, |dict| dict.init_time());
+        #[cfg(feature = "std")]
+        {
+            stats.match_search_time = total_time.saturating_sub(stats.dict_init_time);
+        }
+
+        (compressed, stats)
+    }
+
+    /// Compresses `data`, starting at `start`, with the data before it used as look-behind,
+    /// returning the compressed data in a new [`Vec`].
+    #[cfg(feature = "std")]
+    pub(crate) fn compress_range_to_vec(&mut self, data: &[u8], start: usize) -> Vec<u8> {
+        let mut dest = Vec::with_capacity(prs_calculate_max_compressed_size(data.len() - start));
+        unsafe {
+            let compressed_len =
+                self.compress_range_unsafe(data.as_ptr(), data.len(), start, dest.as_mut_ptr());
+            dest.set_len(compressed_len);
+        }
+
+        dest
+    }
+
+    /// Compresses the given data in `src`, placing it in `dest`.
+    ///
+    /// # Parameters
+    ///
+    /// - `src`: A pointer to the decompressed data.
+    /// - `src_len`: Length of the decompressed data.
+    /// - `dest`: A pointer to the compressed data to be written.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes written to `dest`.
+    ///
+    /// # Safety
+    ///
+    /// It's safe as long as `dest` has sufficient length (max length: [`crate::util::prs_calculate_max_compressed_size`])
+    /// and the remaining parameters are valid.
+    pub unsafe fn compress_unsafe(
+        &mut self,
+        src: *const u8,
+        src_len: usize,
+        dest: *mut u8,
+    ) -> usize {
+        self.compress_range_unsafe(src, src_len, 0, dest)
+    }
+
+    /// Same as [`PrsCompressor::compress_unsafe`], but returns [`AllocError`] if either allocator
+    /// fails to allocate the dictionary or scratch buffers, instead of panicking.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes written to `dest`. Nothing is written to `dest` on failure.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`PrsCompressor::compress_unsafe`].
+    pub unsafe fn try_compress_unsafe(
+        &mut self,
+        src: *const u8,
+        src_len: usize,
+        dest: *mut u8,
+    ) -> Result<usize, AllocError> {
+        self.try_compress_range_unsafe(src, src_len, 0, dest)
+    }
+
+    /// Same as [`PrsCompressor::compress_unsafe`], but only compresses the data from `start` onwards,
+    /// with the data before it used as look-behind.
+    unsafe fn compress_range_unsafe(
+        &mut self,
+        src: *const u8,
+        src_len: usize,
+        start: usize,
+        dest: *mut u8,
+    ) -> usize {
+        self.try_compress_range_unsafe(src, src_len, start, dest)
+            .unwrap()
+    }
+
+    /// Fallible version of [`PrsCompressor::compress_range_unsafe`].
+    /// Memory is allocated before anything is written to `dest`.
+    unsafe fn try_compress_range_unsafe(
+        &mut self,
+        src: *const u8,
+        src_len: usize,
+        start: usize,
+        dest: *mut u8,
+    ) -> Result<usize, AllocError> {
+        let orig_dest = dest as usize;
+        let mut writer = CompressWriter::new(dest);
+        let mut source_ofs = start;
+
+        match self.options.level {
+            CompressionLevel::Greedy if src_len < SMALL_INPUT_THRESHOLD => {
+                compress_range_small::<false>(src, src_len, &mut source_ofs, &mut writer)
+            }
+            CompressionLevel::Lazy if src_len < SMALL_INPUT_THRESHOLD => {
+                compress_range_small::<true>(src, src_len, &mut source_ofs, &mut writer)
+            }
+            CompressionLevel::Greedy => compress_range::<false, L, S>(
+                self.dict(src_len)?,
+                src,
+                src_len,
+                &mut source_ofs,
+                true,
+                &mut writer,
+            ),
+            CompressionLevel::Lazy => compress_range::<true, L, S>(
+                self.dict(src_len)?,
+                src,
+                src_len,
+                &mut source_ofs,
+                true,
+                &mut writer,
+            ),
+            CompressionLevel::Fast => {
+                let max_candidates = self.options.max_candidates.max(1);
+                let chain = self.chain()?;
+                for x in start.saturating_sub(MAX_OFFSET)..start.min(src_len.saturating_sub(1)) {
+                    chain.insert(src, x);
+                }
+
+                compress_range_fast(
+                    chain,
+                    src,
+                    src_len,
+                    &mut source_ofs,
+                    max_candidates,
+                    &mut writer,
+                )
+            }
+            CompressionLevel::Sega => {
+                compress_range_sega(src, src_len, &mut source_ofs, &mut writer)
+            }
+            CompressionLevel::Optimal => {
+                self.dict(src_len)?;
+                self.reserve_steps(src_len - start + 1)?;
+                compress_range_optimal(
+                    self.dict.as_mut().unwrap_unchecked(),
+                    src,
+                    src_len,
+                    &mut source_ofs,
+                    self.steps.as_mut().unwrap_unchecked(),
+                    &mut writer,
+                )
+            }
+        }
+
+        writer.write_terminator();
+        Ok(writer.dest as usize - orig_dest)
+    }
+
+    /// Returns the dictionary, (re)allocating it if it is too small for `src_len` bytes of data.
+    fn dict(&mut self, src_len: usize) -> Result<&mut CompDict<L, S>, AllocError> {
+        let data_len = src_len.min(WINDOW_SIZE);
+        if !self
+            .dict
+            .as_ref()
+            .is_some_and(|dict| dict.data_len() >= data_len)
+        {
+            // Free the old dictionary first, to avoid holding both at once.
+            self.dict = None;
+            self.dict = Some(CompDict::try_new_in(
+                data_len,
+                self.long_lived_allocator,
+                self.short_lived_allocator,
+            )?);
+        }
+
+        let dict = unsafe { self.dict.as_mut().unwrap_unchecked() };
+        #[cfg(feature = "std")]
+        dict.set_collect_stats(self.collect_stats);
+        Ok(dict)
+    }
+
+    /// Returns the (cleared) hash chain, allocating it if needed.
+    fn chain(&mut self) -> Result<&mut HashChain<L>, AllocError> {
+        if self.chain.is_none() {
+            self.chain = Some(HashChain::try_new_in(self.long_lived_allocator)?);
+        }
+
+        let chain = unsafe { self.chain.as_mut().unwrap_unchecked() };
+        chain.clear();
+        Ok(chain)
+    }
+
+    /// Ensures the optimal parser's scratch space has at least `len` elements.
+    fn reserve_steps(&mut self, len: usize) -> Result<(), AllocError> {
+        if !self.steps.as_ref().is_some_and(|steps| steps.len() >= len) {
+            self.steps = None;
+            self.steps = Some(unsafe {
+                AllocBox::try_new_zeroed_slice_in(len, self.short_lived_allocator)?
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::impls::decomp::decompress::prs_decompress_checked_with_dictionary;
+    use crate::test_prelude::LimitedAllocator;
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    use crate::test_prelude::SparseBuffer;
+    use alloc::vec;
+
+    #[test]
+    fn try_compress_reports_allocation_failure() {
+        // Smaller inputs don't allocate with some levels.
+        let src: Vec<u8> = (0..SMALL_INPUT_THRESHOLD as u32)
+            .map(|x| (x % 7) as u8)
+            .collect();
+        let mut dest = vec![0_u8; prs_calculate_max_compressed_size(src.len())];
+
+        for level in [
+            CompressionLevel::Greedy,
+            CompressionLevel::Lazy,
+            CompressionLevel::Fast,
+            CompressionLevel::Optimal,
+        ] {
+            let allocator = LimitedAllocator::new(1024);
+            let mut compressor =
+                PrsCompressor::new_in(CompressionOptions::new(level), &allocator, &allocator);
+            let result = unsafe {
+                compressor.try_compress_unsafe(src.as_ptr(), src.len(), dest.as_mut_ptr())
+            };
+            assert_eq!(result, Err(AllocError), "{level:?}");
+
+            let allocator = LimitedAllocator::new(usize::MAX);
+            let mut compressor =
+                PrsCompressor::new_in(CompressionOptions::new(level), &allocator, &allocator);
+            let compressed_len = unsafe {
+                compressor.try_compress_unsafe(src.as_ptr(), src.len(), dest.as_mut_ptr())
+            }
+            .unwrap();
+            let expected =
+                PrsCompressor::with_options(CompressionOptions::new(level)).compress_to_vec(&src);
+            assert_eq!(&dest[..compressed_len], expected.as_slice(), "{level:?}");
+        }
+    }
+
+    /// Compresses `src` with [`compress_range`], regardless of its size.
+    unsafe fn compress_with_dict<const LAZY: bool>(src: &[u8]) -> Vec<u8> {
+        let mut dest = vec![0_u8; prs_calculate_max_compressed_size(src.len())];
+        let mut dict = CompDict::new(WINDOW_SIZE);
+        let mut writer = CompressWriter::new(dest.as_mut_ptr());
+        let mut source_ofs = 0;
+        compress_range::<LAZY, Global, Global>(
+            &mut dict,
+            src.as_ptr(),
+            src.len(),
+            &mut source_ofs,
+            true,
+            &mut writer,
+        );
+        writer.write_terminator();
+
+        dest.truncate(writer.dest as usize - dest.as_ptr() as usize);
+        dest
+    }
+
+    #[test]
+    fn small_inputs_match_dictionary_compression_without_allocating() {
+        // Few distinct bytes with zero runs, so there are many matches to choose from.
+        let mut seed = 1_u32;
+        let data: Vec<u8> = (0..SMALL_INPUT_THRESHOLD - 1)
+            .map(|x| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                if (x / 300) % 3 == 0 {
+                    0
+                } else {
+                    (seed >> 29) as u8
+                }
+            })
+            .collect();
+
+        for len in [0, 1, 2, 3, 100, 256, 257, 1000, data.len()] {
+            let src = &data[..len];
+            let allocator = LimitedAllocator::new(0);
+            for (level, expected) in [
+                (CompressionLevel::Greedy, unsafe {
+                    compress_with_dict::<false>(src)
+                }),
+                (CompressionLevel::Lazy, unsafe {
+                    compress_with_dict::<true>(src)
+                }),
+            ] {
+                let mut compressor =
+                    PrsCompressor::new_in(CompressionOptions::new(level), &allocator, &allocator);
+                let compressed = compressor.compress_to_vec(src);
+                assert_eq!(compressed, expected, "{level:?} {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn compress_with_dictionary_matches_joined_compression() {
+        let data: Vec<u8> = (0..0x10000_usize)
+            .map(|x| ((x % 251) ^ (x / 97)) as u8)
+            .collect();
+        let (dictionary, src) = data.split_at(0x4000);
+        let joined = &data[dictionary.len() - MAX_OFFSET..];
+
+        for level in [
+            CompressionLevel::Greedy,
+            CompressionLevel::Lazy,
+            CompressionLevel::Fast,
+            CompressionLevel::Optimal,
+            CompressionLevel::Sega,
+        ] {
+            let mut compressor = PrsCompressor::with_options(CompressionOptions::new(level));
+            let max_len = prs_calculate_max_compressed_size(src.len());
+            let mut expected = vec![0_u8; max_len];
+            let expected_len = unsafe {
+                compressor.compress_range_unsafe(
+                    joined.as_ptr(),
+                    joined.len(),
+                    MAX_OFFSET,
+                    expected.as_mut_ptr(),
+                )
+            };
+
+            // Copies may span the end of the dictionary, so the output is no larger.
+            let mut compressed = vec![MaybeUninit::uninit(); max_len];
+            let compressed_len = compressor
+                .compress_with_dictionary(dictionary, src, &mut compressed)
+                .unwrap();
+            let compressed: Vec<u8> = compressed[..compressed_len]
+                .iter()
+                .map(|x| unsafe { x.assume_init() })
+                .collect();
+            assert_eq!(compressed, expected[..expected_len], "{level:?}");
+
+            let mut decompressed = vec![0_u8; src.len()];
+            assert_eq!(
+                prs_decompress_checked_with_dictionary(dictionary, &compressed, &mut decompressed),
+                Ok(src.len()),
+                "{level:?}"
+            );
+            assert_eq!(decompressed, src, "{level:?}");
+        }
+    }
+
+    #[test]
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    fn can_compress_beyond_4gib() {
+        let start = 0x1_0000_0000 + 0x1234;
+        let len = 0x4000;
+        let mut data = SparseBuffer::new(start + len);
+        for (x, byte) in data[start - MAX_OFFSET..].iter_mut().enumerate() {
+            *byte = ((x % 251) ^ (x / 97)) as u8;
+        }
+
+        // The same bytes, at the start of a regular buffer.
+        let reference: Vec<u8> = data[start - MAX_OFFSET..].to_vec();
+        let dictionary = &data[start - MAX_OFFSET..start];
+        for level in [
+            CompressionLevel::Greedy,
+            CompressionLevel::Lazy,
+            CompressionLevel::Fast,
+            CompressionLevel::Optimal,
+        ] {
+            let mut compressor = PrsCompressor::with_options(CompressionOptions::new(level));
+            let mut compressed = vec![0_u8; prs_calculate_max_compressed_size(len)];
+            let compressed_len = unsafe {
+                compressor.compress_range_unsafe(
+                    data.as_ptr(),
+                    data.len(),
+                    start,
+                    compressed.as_mut_ptr(),
+                )
+            };
+
+            let mut expected = vec![0_u8; prs_calculate_max_compressed_size(len)];
+            let expected_len = unsafe {
+                compressor.compress_range_unsafe(
+                    reference.as_ptr(),
+                    reference.len(),
+                    MAX_OFFSET,
+                    expected.as_mut_ptr(),
+                )
+            };
+            assert_eq!(
+                compressed[..compressed_len],
+                expected[..expected_len],
+                "{level:?}"
+            );
+
+            let mut decompressed = vec![0_u8; len];
+            assert_eq!(
+                prs_decompress_checked_with_dictionary(
+                    dictionary,
+                    &compressed[..compressed_len],
+                    &mut decompressed
+                ),
+                Ok(len),
+                "{level:?}"
+            );
+            assert_eq!(decompressed, data[start..], "{level:?}");
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use super::comp_dict::CompDict;
+use super::compress::{compress_range, CompressWriter, COPY_MAX_LENGTH, MAX_OFFSET, WINDOW_SIZE};
+use crate::util::prs_calculate_max_compressed_size;
+use alloc::vec::Vec;
+use std::io::{self, Write};
+
+/// Streaming PRS compressor, which compresses data as it is written and passes the compressed
+/// data on to an underlying [`Write`].
+///
+/// Data is compressed one `WINDOW_SIZE` block at a time, keeping the last 0x1FFF bytes of each block
+/// around as look-behind for the next one. Memory usage is therefore constant, regardless of how
+/// much data is written.
+///
+/// # Remarks
+///
+/// [`PrsEncoder::finish`] must be called once all data is written, in order to compress the remaining
+/// data and write the terminator. Dropping the encoder without calling it produces an incomplete stream.
+///
+/// The output is identical to that of [`crate::comp::prs_compress`].
+pub struct PrsEncoder<W: Write> {
+    inner: W,
+    dict: CompDict,
+    /// Uncompressed data. Bytes before `source_ofs` have been compressed, and are kept as look-behind.
+    source: Vec<u8>,
+    /// Offset of the next byte in `source` to compress.
+    source_ofs: usize,
+    /// Compressed data which has not yet been written to `inner`.
+    output: Vec<u8>,
+    /// Offset of the control byte currently being filled in `output`.
+    control_byte_ofs: usize,
+    /// The current bit position in the control byte.
+    control_bit_position: usize,
+}
+
+impl<W: Write> PrsEncoder<W> {
+    /// Creates a new encoder which writes the compressed data to `inner`.
+    pub fn new(inner: W) -> Self {
+        let mut output = Vec::with_capacity(prs_calculate_max_compressed_size(WINDOW_SIZE));
+        output.push(0); // first control byte
+
+        Self {
+            inner,
+            dict: CompDict::new(WINDOW_SIZE),
+            source: Vec::with_capacity(WINDOW_SIZE),
+            source_ofs: 0,
+            output,
+            control_byte_ofs: 0,
+            control_bit_position: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    ///
+    /// Writing to the underlying writer mid-stream will corrupt the compressed output.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Compresses all remaining data, writes the terminator and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress(true);
+        unsafe {
+            let mut writer = self.begin_write(0);
+            writer.write_terminator();
+            self.end_write(writer);
+        }
+
+        self.inner.write_all(&self.output)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Compresses the buffered data. If `is_final` is false, the last `COPY_MAX_LENGTH` bytes
+    /// are left uncompressed, as more data may follow them.
+    fn compress(&mut self, is_final: bool) {
+        let source_len = self.source.len();
+        let mut source_ofs = self.source_ofs;
+
+        unsafe {
+            let mut writer = self.begin_write(source_len - source_ofs);
+            compress_range::<false, _, _>(
+                &mut self.dict,
+                self.source.as_ptr(),
+                source_len,
+                &mut source_ofs,
+                is_final,
+                &mut writer,
+            );
+            self.end_write(writer);
+        }
+
+        // Keep the look-behind for the next block.
+        let keep_from = source_ofs.saturating_sub(MAX_OFFSET);
+        self.source.drain(..keep_from);
+        self.source_ofs = source_ofs - keep_from;
+    }
+
+    /// Returns a [`CompressWriter`] pointing at the end of the pending output,
+    /// with enough space reserved for compressing `num_bytes` bytes.
+    ///
+    /// The writer must be passed to [`PrsEncoder::end_write`] before `output` is modified again.
+    unsafe fn begin_write(&mut self, num_bytes: usize) -> CompressWriter {
+        self.output
+            .reserve(prs_calculate_max_compressed_size(num_bytes));
+
+        let base = self.output.as_mut_ptr();
+        CompressWriter {
+            dest: base.add(self.output.len()),
+            control_byte_ptr: base.add(self.control_byte_ofs),
+            control_bit_position: self.control_bit_position,
+        }
+    }
+
+    /// Updates the pending output with the data written by a writer from [`PrsEncoder::begin_write`].
+    unsafe fn end_write(&mut self, writer: CompressWriter) {
+        let base = self.output.as_mut_ptr();
+        self.output.set_len(writer.dest as usize - base as usize);
+        self.control_byte_ofs = writer.control_byte_ptr as usize - base as usize;
+        self.control_bit_position = writer.control_bit_position;
+    }
+
+    /// Writes all compressed data before the current control byte to the underlying writer.
+    /// The current control byte and data after it can still change, so are kept.
+    fn write_completed_output(&mut self) -> io::Result<()> {
+        self.inner
+            .write_all(&self.output[..self.control_byte_ofs])?;
+        self.output.drain(..self.control_byte_ofs);
+        self.control_byte_ofs = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for PrsEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Only accept as much data as fits in a window, to keep memory usage bounded.
+        let num_bytes = buf.len().min(WINDOW_SIZE - self.source.len());
+        self.source.extend_from_slice(&buf[..num_bytes]);
+
+        if self.source.len() == WINDOW_SIZE {
+            self.compress(false);
+            self.write_completed_output()?;
+        }
+
+        Ok(num_bytes)
+    }
+
+    /// Writes all compressed data that is complete to the underlying writer, and flushes it.
+    ///
+    /// Buffered uncompressed data is not compressed until more data is written or [`PrsEncoder::finish`]
+    /// is called, since the compressor needs to look ahead up to `COPY_MAX_LENGTH` bytes.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_completed_output()?;
+        self.inner.flush()
+    }
+}
+
+// Ensure each block makes progress, i.e. there is data left to compress after the look-behind.
+const _: () = assert!(WINDOW_SIZE > MAX_OFFSET + COPY_MAX_LENGTH as usize);
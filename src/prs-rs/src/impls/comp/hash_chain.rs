@@ -0,0 +1,254 @@
+use super::alloc_box::AllocBox;
+use super::compress::MAX_OFFSET;
+use super::lz77_matcher::{Lz77Match, Lz77Parameters};
+use super::scratch::NoAlloc;
+use crate::prelude::{AllocError, Allocator};
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+use core::ptr::slice_from_raw_parts_mut;
+
+/// Number of possible 2 byte keys.
+const NUM_KEYS: usize = 65536;
+
+/// Number of previous positions remembered by the chain.
+/// Must be a power of 2, and greater than [`MAX_OFFSET`].
+const CHAIN_SIZE: usize = 0x2000;
+
+/// Hash chain for PRS compression, used by [`crate::comp::CompressionLevel::Fast`].
+///
+/// Unlike [`super::comp_dict::CompDict`], which is built up front for a whole window, this is
+/// updated one position at a time as data is compressed, so there is no setup cost.
+///
+/// For each 2 byte key, `head` stores the last position the key was seen at, and `prev` links each
+/// position to the previous position with the same key. Candidates are therefore walked nearest first,
+/// which makes it cheap to stop after a fixed number of them.
+///
+/// Matches are never further back than [`Lz77Parameters::MAX_OFFSET`], so `prev` only needs to
+/// remember the last [`CHAIN_SIZE`] positions.
+pub struct HashChain<L: Allocator + Copy> {
+    /// Last position (+ 1) of each key. 0 if the key has not been seen.
+    head: AllocBox<[usize], L>,
+    /// Previous position (+ 1) with the same key as the position at `index % CHAIN_SIZE`.
+    /// 0 if there is none.
+    prev: AllocBox<[usize], L>,
+}
+
+impl<L: Allocator + Copy> HashChain<L> {
+    /// Creates a new, empty [`HashChain`].
+    ///
+    /// # Parameters
+    ///
+    /// - `allocator`: The allocator to use for the chain's memory.
+    ///
+    /// # Panics
+    ///
+    /// If the allocator fails to allocate; see [`HashChain::try_new_in`] for a non-panicking version.
+    pub fn new_in(allocator: L) -> Self {
+        Self::try_new_in(allocator).unwrap()
+    }
+
+    /// Same as [`HashChain::new_in`], but returns [`AllocError`] if the allocator fails to allocate.
+    pub fn try_new_in(allocator: L) -> Result<Self, AllocError> {
+        unsafe {
+            Ok(Self {
+                head: AllocBox::try_new_zeroed_slice_in(NUM_KEYS, allocator)?,
+                prev: AllocBox::try_new_zeroed_slice_in(CHAIN_SIZE, allocator)?,
+            })
+        }
+    }
+
+    /// Removes all positions from the chain, so it can be reused for new data.
+    pub fn clear(&mut self) {
+        // Entries in `prev` are only reachable via `head`, so don't need clearing.
+        self.head.fill(0);
+    }
+
+    /// Adds the 2 byte key at `source_index` to the chain.
+    ///
+    /// # Safety
+    ///
+    /// `source_index + 1` must be within bounds of `source_ptr`.
+    /// Positions must be inserted in ascending order.
+    #[inline(always)]
+    pub unsafe fn insert(&mut self, source_ptr: *const u8, source_index: usize) {
+        let key = read_unaligned(source_ptr.add(source_index) as *const u16) as usize;
+        *self.prev.get_unchecked_mut(source_index % CHAIN_SIZE) = *self.head.get_unchecked(key);
+        *self.head.get_unchecked_mut(key) = source_index + 1;
+    }
+
+    /// Returns the longest match for the data at `source_index`, checking at most `max_candidates`
+    /// previously inserted positions, nearest first.
+    ///
+    /// # Parameters
+    ///
+    /// - `source_ptr`: The data where the match is to be searched.
+    /// - `source_len`: The length of the data.
+    /// - `source_index`: The index of the current byte in the source.
+    /// - `max_candidates`: Maximum number of previous positions to check.
+    ///
+    /// # Safety
+    ///
+    /// `source_index + 1` must be less than `source_len`, and all positions before
+    /// `source_index` (up to `P::MAX_OFFSET` bytes back) must have been inserted.
+    #[inline(always)]
+    pub unsafe fn find_longest_match<P: Lz77Parameters>(
+        &self,
+        source_ptr: *const u8,
+        source_len: usize,
+        source_index: usize,
+        max_candidates: usize,
+    ) -> Lz77Match {
+        let mut best_match = Lz77Match {
+            offset: 0,
+            length: 0,
+        };
+
+        let key = read_unaligned(source_ptr.add(source_index) as *const u16) as usize;
+        let max_match_length = P::MAX_LENGTH.min(source_len - source_index);
+        let mut candidate = *self.head.get_unchecked(key);
+
+        for _ in 0..max_candidates {
+            // Stop at the end of the chain, or once out of range.
+            if candidate == 0 || source_index - (candidate - 1) > P::MAX_OFFSET {
+                break;
+            }
+
+            let match_offset = candidate - 1;
+            candidate = *self.prev.get_unchecked(match_offset % CHAIN_SIZE);
+
+            // We start having matched 2, and match usize at a time while we can.
+            let mut match_length = 2;
+            let offset_src_ptr = source_ptr.add(match_offset);
+            let offset_dst_ptr = source_ptr.add(source_index);
+            while match_length + size_of::<usize>() <= max_match_length
+                && read_unaligned(offset_src_ptr.add(match_length) as *const usize)
+                    == read_unaligned(offset_dst_ptr.add(match_length) as *const usize)
+            {
+                match_length += size_of::<usize>();
+            }
+
+            while match_length < max_match_length
+                && *offset_src_ptr.add(match_length) == *offset_dst_ptr.add(match_length)
+            {
+                match_length += 1;
+            }
+
+            // Update the best match if this match is longer
+            if match_length > best_match.length {
+                best_match.length = match_length;
+                best_match.offset = match_offset as isize - source_index as isize;
+
+                if match_length == max_match_length {
+                    break;
+                }
+            }
+        }
+
+        best_match
+    }
+}
+
+impl HashChain<NoAlloc> {
+    /// Number of bytes [`HashChain::from_scratch`] needs.
+    pub(crate) const SCRATCH_SIZE: usize = size_of::<usize>() * (NUM_KEYS + CHAIN_SIZE);
+
+    /// Creates a new, empty [`HashChain`] in caller-provided memory.
+    ///
+    /// # Safety
+    ///
+    /// `scratch` must be aligned to `usize`, be valid for [`HashChain::SCRATCH_SIZE`] bytes,
+    /// and outlive the chain.
+    pub(crate) unsafe fn from_scratch(scratch: *mut u8) -> Self {
+        let head = scratch as *mut usize;
+        let prev = head.add(NUM_KEYS);
+        let mut chain = Self {
+            head: AllocBox::from_raw_in(slice_from_raw_parts_mut(head, NUM_KEYS), NoAlloc),
+            prev: AllocBox::from_raw_in(slice_from_raw_parts_mut(prev, CHAIN_SIZE), NoAlloc),
+        };
+        chain.clear();
+        chain
+    }
+}
+
+// `prev` must hold every position a match can reference.
+const _: () = assert!(CHAIN_SIZE.is_power_of_two() && CHAIN_SIZE > MAX_OFFSET);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::Global;
+
+    struct CompressParameters;
+    impl Lz77Parameters for CompressParameters {
+        const MAX_OFFSET: usize = 0x1FFF;
+        const MAX_LENGTH: usize = 256;
+        const MAX_SHORT_OFFSET: usize = 0x100;
+    }
+
+    #[test]
+    fn finds_nearest_longest_match() {
+        let data = b"abcabcabcabcabc";
+        let mut chain = HashChain::new_in(Global);
+        unsafe {
+            for x in 0..3 {
+                chain.insert(data.as_ptr(), x);
+            }
+
+            let result = chain.find_longest_match::<CompressParameters>(
+                data.as_ptr(),
+                data.len(),
+                3,
+                usize::MAX,
+            );
+            assert_eq!(result.length, 12);
+            assert_eq!(result.offset, -3);
+        }
+    }
+
+    #[test]
+    fn max_candidates_checks_nearest_first() {
+        // "abcd" is 0x200 bytes back, "abc" is 4 bytes back.
+        let mut data = [0_u8; 0x210];
+        data[0x8..0xC].copy_from_slice(b"abcd");
+        data[0x204..0x207].copy_from_slice(b"abc");
+        data[0x208..0x20D].copy_from_slice(b"abcd\xFF");
+        let mut chain = HashChain::new_in(Global);
+        unsafe {
+            for x in 0..0x208 {
+                chain.insert(data.as_ptr(), x);
+            }
+
+            // Only the nearest candidate is checked, so the longer match further back is missed.
+            let result =
+                chain.find_longest_match::<CompressParameters>(data.as_ptr(), data.len(), 0x208, 1);
+            assert_eq!(result.length, 3);
+            assert_eq!(result.offset, -4);
+
+            let result =
+                chain.find_longest_match::<CompressParameters>(data.as_ptr(), data.len(), 0x208, 2);
+            assert_eq!(result.length, 4);
+            assert_eq!(result.offset, -0x200);
+        }
+    }
+
+    #[test]
+    fn ignores_matches_out_of_range() {
+        let mut data = [0_u8; 0x2010];
+        data[0x0..0x4].copy_from_slice(b"abcd");
+        data[0x2000..0x2004].copy_from_slice(b"abcd");
+        let mut chain = HashChain::new_in(Global);
+        unsafe {
+            for x in 0..0x2000 {
+                chain.insert(data.as_ptr(), x);
+            }
+
+            let result = chain.find_longest_match::<CompressParameters>(
+                data.as_ptr(),
+                data.len(),
+                0x2000,
+                usize::MAX,
+            );
+            assert_eq!(result.length, 0);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use super::comp_dict::CompDict;
+use super::match_length::match_length as extend_match;
 use crate::prelude::Allocator;
 use core::mem::size_of;
 use core::ptr::read_unaligned;
@@ -14,6 +15,9 @@ pub trait Lz77Parameters {
     const MAX_OFFSET: usize;
     /// Maximum length of the match.
     const MAX_LENGTH: usize;
+    /// Maximum offset (from the current position) of a short (cheaper) match.
+    /// Used by [`lz77_get_longest_matches`].
+    const MAX_SHORT_OFFSET: usize;
 }
 
 /// Searches back up to 'COPY_MAX_LENGTH' bytes and returns the length of the longest matching
@@ -40,6 +44,11 @@ pub unsafe fn lz77_get_longest_match_fast<
     source_ptr: *const u8,
     source_index: usize,
 ) -> Lz77Match {
+    // Runs (e.g. padding) have many candidates with the same key, skip checking them all.
+    if let Some(run_match) = lz77_get_run_match::<P>(source_ptr, source_index) {
+        return run_match;
+    }
+
     let mut best_match = Lz77Match {
         offset: 0,
         length: 0,
@@ -52,9 +61,10 @@ pub unsafe fn lz77_get_longest_match_fast<
     let key = read_unaligned(source_ptr.add(source_index) as *const u16);
 
     // Retrieve possible match offsets from the dictionary
+    let window_start = dict.window_start();
     let offsets = dict.get_item(key, min_offset, source_index.saturating_sub(1));
     for &match_offset in offsets.iter().rev() {
-        let match_offset = match_offset as usize;
+        let match_offset = window_start + match_offset as usize;
 
         // Determine the length of the match
         let mut match_length = 2;
@@ -70,25 +80,8 @@ pub unsafe fn lz77_get_longest_match_fast<
             match_length +=
                 (*offset_src_ptr.add(match_length) == *offset_dst_ptr.add(match_length)) as usize;
         } else {
-            match_length = 4;
-
-            // We are usize aligned (for perf) and MAX_LENGTH should be divisible by usize.
-            // Therefore there is no risk of running out of bounds here in the usize matching.
-            debug_assert!(P::MAX_LENGTH % size_of::<usize>() == 0);
-
-            // First 8 bytes match.
-            while match_length < (P::MAX_LENGTH - size_of::<u32>())
-                && read_unaligned(offset_src_ptr.add(match_length) as *const usize)
-                    == read_unaligned(offset_dst_ptr.add(match_length) as *const usize)
-            {
-                match_length += size_of::<usize>();
-            }
-
-            while match_length < P::MAX_LENGTH
-                && *offset_src_ptr.add(match_length) == *offset_dst_ptr.add(match_length)
-            {
-                match_length += 1;
-            }
+            // First 4 bytes match.
+            match_length = extend_match(offset_src_ptr, offset_dst_ptr, 4, P::MAX_LENGTH);
         }
 
         // Update the best match if this match is longer
@@ -105,6 +98,55 @@ pub unsafe fn lz77_get_longest_match_fast<
     best_match
 }
 
+/// Returns a max length (`P::MAX_LENGTH`) match at offset -1 or -2, if the data at `source_index`
+/// continues a run of a single repeated byte, or a repeated pair of bytes.
+///
+/// Long runs, like zero padding, put thousands of locations under the same key in the [`CompDict`].
+/// The match finders check the nearest location first, and stop at a max length match, so this
+/// returns the same match they would, without going through the dictionary.
+///
+/// # Parameters
+///
+/// - `source_ptr`: The data where the match is to be searched.
+/// - `source_index`: The index of the current byte in the source.
+///
+/// # Safety
+///
+/// At least `P::MAX_LENGTH` bytes must be readable from `source_index`.
+#[inline(always)]
+pub unsafe fn lz77_get_run_match<P: Lz77Parameters>(
+    source_ptr: *const u8,
+    source_index: usize,
+) -> Option<Lz77Match> {
+    // We compare usize at a time, and MAX_LENGTH should be divisible by usize.
+    debug_assert!(P::MAX_LENGTH % size_of::<usize>() == 0);
+
+    let offset_dst_ptr = source_ptr.add(source_index);
+    for distance in 1..=2 {
+        if source_index < distance {
+            break;
+        }
+
+        let offset_src_ptr = offset_dst_ptr.sub(distance);
+        let mut match_length = 0;
+        while match_length < P::MAX_LENGTH
+            && read_unaligned(offset_src_ptr.add(match_length) as *const usize)
+                == read_unaligned(offset_dst_ptr.add(match_length) as *const usize)
+        {
+            match_length += size_of::<usize>();
+        }
+
+        if match_length == P::MAX_LENGTH {
+            return Some(Lz77Match {
+                offset: -(distance as isize),
+                length: P::MAX_LENGTH,
+            });
+        }
+    }
+
+    None
+}
+
 /// Searches back up to 'COPY_MAX_LENGTH' bytes and returns the length of the longest matching
 /// sequence of bytes. This is the slow version that ensures we don't overrun past the end of file.
 ///
@@ -144,9 +186,10 @@ pub unsafe fn lz77_get_longest_match_slow<
     let max_match_length = P::MAX_LENGTH.min(source_len - source_index);
 
     // Retrieve possible match offsets from the dictionary
+    let window_start = dict.window_start();
     let offsets = dict.get_item(key, min_offset, source_index.saturating_sub(1));
     for &match_offset in offsets.iter().rev() {
-        let match_offset = match_offset as usize;
+        let match_offset = window_start + match_offset as usize;
 
         // We start having matched 2 and match byte by byte
         let mut match_length = 2;
@@ -172,6 +215,145 @@ pub unsafe fn lz77_get_longest_match_slow<
     best_match
 }
 
+/// Searches back up to `P::MAX_OFFSET` bytes and returns both the longest match, and the longest
+/// match within `P::MAX_SHORT_OFFSET` bytes. Unlike [`lz77_get_longest_match_fast`], this checks
+/// every candidate offset, as needed by the optimal parser.
+///
+/// # Parameters
+///
+/// - `dict`: The dictionary used to speed up computation.
+/// - `source_ptr`: The data where the match is to be searched.
+/// - `source_len`: The length of the data.
+/// - `source_index`: The index of the current byte in the source.
+/// - `short_max_length`: Stop searching for short matches once one of this length is found.
+///
+/// # Returns
+///
+/// The longest match, and the longest match within `P::MAX_SHORT_OFFSET`, in that order.
+///
+/// # Safety
+///
+/// Should be safe provided `dict` is initialized with `source` and composed of valid data.
+pub unsafe fn lz77_get_longest_matches<
+    P: Lz77Parameters,
+    L: Allocator + Copy,
+    S: Allocator + Copy,
+>(
+    dict: &mut CompDict<L, S>,
+    source_ptr: *const u8,
+    source_len: usize,
+    source_index: usize,
+    short_max_length: usize,
+) -> (Lz77Match, Lz77Match) {
+    let mut best_match = Lz77Match {
+        offset: 0,
+        length: 0,
+    };
+    let mut best_short_match = Lz77Match {
+        offset: 0,
+        length: 0,
+    };
+
+    // Calculate the minimum offsets to consider for a match
+    let min_offset = source_index.saturating_sub(P::MAX_OFFSET);
+    let min_short_offset = source_index.saturating_sub(P::MAX_SHORT_OFFSET);
+
+    // Read the 2-byte sequence from source at the current index
+    let key = read_unaligned(source_ptr.add(source_index) as *const u16);
+
+    // Calculate the maximum possible match length
+    let max_match_length = P::MAX_LENGTH.min(source_len - source_index);
+    let short_max_length = short_max_length.min(max_match_length);
+
+    // Retrieve possible match offsets from the dictionary
+    let window_start = dict.window_start();
+    let offsets = dict.get_item(key, min_offset, source_index.saturating_sub(1));
+    for &match_offset in offsets.iter().rev() {
+        let match_offset = window_start + match_offset as usize;
+
+        // We start having matched 2.
+        let match_length = extend_match(
+            source_ptr.add(match_offset),
+            source_ptr.add(source_index),
+            2,
+            max_match_length,
+        );
+
+        let offset = match_offset as isize - source_index as isize;
+        if match_offset >= min_short_offset && match_length > best_short_match.length {
+            best_short_match.length = match_length;
+            best_short_match.offset = offset;
+        }
+
+        // Update the best match if this match is longer
+        if match_length > best_match.length {
+            best_match.length = match_length;
+            best_match.offset = offset;
+        }
+
+        // Offsets are checked nearest first, so once the best match is as long as possible,
+        // the best short match can't improve either.
+        if best_match.length == max_match_length
+            && (best_short_match.length >= short_max_length || match_offset < min_short_offset)
+        {
+            break;
+        }
+    }
+
+    (best_match, best_short_match)
+}
+
+/// Returns the longest match for the data at `source_index`, by comparing against every position
+/// up to `P::MAX_OFFSET` bytes back, nearest first. On ties, the nearest match is kept.
+///
+/// This is how SEGA's original compressor chooses matches. It doesn't need a dictionary,
+/// but is only practical for small values of `P::MAX_OFFSET`.
+///
+/// # Parameters
+///
+/// - `source_ptr`: The data where the match is to be searched.
+/// - `source_len`: The length of the data.
+/// - `source_index`: The index of the current byte in the source.
+///
+/// # Safety
+///
+/// `source_index` must be less than `source_len`.
+#[inline(always)]
+pub unsafe fn lz77_get_longest_match_brute_force<P: Lz77Parameters>(
+    source_ptr: *const u8,
+    source_len: usize,
+    source_index: usize,
+) -> Lz77Match {
+    let mut best_match = Lz77Match {
+        offset: 0,
+        length: 0,
+    };
+
+    let max_match_length = P::MAX_LENGTH.min(source_len - source_index);
+    let offset_dst_ptr = source_ptr.add(source_index);
+    for match_offset in (source_index.saturating_sub(P::MAX_OFFSET)..source_index).rev() {
+        let offset_src_ptr = source_ptr.add(match_offset);
+        let mut match_length = 0;
+        while match_length < max_match_length
+            && *offset_src_ptr.add(match_length) == *offset_dst_ptr.add(match_length)
+        {
+            match_length += 1;
+        }
+
+        // Only a longer match replaces the best one, so the nearest wins ties.
+        if match_length > best_match.length {
+            best_match.length = match_length;
+            best_match.offset = match_offset as isize - source_index as isize;
+
+            if match_length == max_match_length {
+                break;
+            }
+        }
+    }
+
+    best_match
+}
+
 /// Represents a match in the LZ77 algorithm.
 pub struct Lz77Match {
     /// Offset of the LZ77 match, expressed as a negative number.
@@ -204,6 +386,31 @@ mod tests {
         assert_eq!(match_result.offset, -3);
     }
 
+    #[test]
+    fn test_longest_matches_finds_short_match() {
+        // "abcd" is 0x200 bytes back, "abc" is 4 bytes back.
+        let mut data = [0_u8; 0x210];
+        data[0x8..0xC].copy_from_slice(b"abcd");
+        data[0x204..0x207].copy_from_slice(b"abc");
+        data[0x208..0x20D].copy_from_slice(b"abcd\xFF");
+        let mut dict = CompDict::new(data.len());
+        unsafe { dict.init(&data, 0) }
+
+        let (long, short) = unsafe {
+            lz77_get_longest_matches::<CompressParameters, Global, Global>(
+                &mut dict,
+                data.as_ptr(),
+                data.len(),
+                0x208,
+                5,
+            )
+        };
+        assert_eq!(long.length, 4);
+        assert_eq!(long.offset, -0x200);
+        assert_eq!(short.length, 3);
+        assert_eq!(short.offset, -4);
+    }
+
     #[test]
     fn test_no_match() {
         let data = b"abcdefgh";
@@ -279,9 +486,78 @@ mod tests {
         assert_eq!(match_result.offset, -2);
     }
 
+    #[test]
+    fn test_run_match_matches_dictionary_search() {
+        // Zero run, run of a repeated pair of bytes, then a run which is too short.
+        let mut data = alloc::vec![0_u8; 0x1000];
+        data[..0x10].copy_from_slice(b"0123456789ABCDEF");
+        for x in (0x800..0xC00).step_by(2) {
+            data[x..x + 2].copy_from_slice(&[0xAB, 0xCD]);
+        }
+        data[0xC00..0xE00].fill(0xFF);
+        data[0xC80] = 0xFE;
+
+        let mut dict = CompDict::new(data.len());
+        unsafe { dict.init(&data, 0) }
+
+        let mut num_runs = [0; 2];
+        for x in 1..data.len() - 0x100 {
+            let run_match = unsafe { lz77_get_run_match::<CompressParameters>(data.as_ptr(), x) };
+            let dict_match = unsafe {
+                lz77_get_longest_match_slow::<CompressParameters, Global, Global>(
+                    &mut dict,
+                    data.as_ptr(),
+                    data.len(),
+                    x,
+                )
+            };
+
+            if let Some(run_match) = run_match {
+                assert_eq!(run_match.length, dict_match.length, "{x:#X}");
+                assert_eq!(run_match.offset, dict_match.offset, "{x:#X}");
+                num_runs[run_match.offset.unsigned_abs() - 1] += 1;
+            } else {
+                assert!(
+                    dict_match.length < 0x100 || dict_match.offset < -2,
+                    "{x:#X}"
+                );
+            }
+        }
+
+        assert!(num_runs[0] > 0 && num_runs[1] > 0);
+    }
+
+    #[test]
+    fn test_brute_force_prefers_nearest_match() {
+        // "abc" is both 8 and 4 bytes back, "abcd" is 0x100 bytes back (out of range).
+        let mut data = [0_u8; 0x110];
+        data[0x4..0x8].copy_from_slice(b"abcd");
+        data[0xFC..0xFF].copy_from_slice(b"abc");
+        data[0x100..0x103].copy_from_slice(b"abc");
+        data[0x104..0x108].copy_from_slice(b"abcd");
+
+        let match_result = unsafe {
+            lz77_get_longest_match_brute_force::<SmallWindowParameters>(
+                data.as_ptr(),
+                data.len(),
+                0x104,
+            )
+        };
+        assert_eq!(match_result.length, 3);
+        assert_eq!(match_result.offset, -4);
+    }
+
+    struct SmallWindowParameters;
+    impl Lz77Parameters for SmallWindowParameters {
+        const MAX_OFFSET: usize = 0xFF;
+        const MAX_LENGTH: usize = 256;
+        const MAX_SHORT_OFFSET: usize = 0x100;
+    }
+
     struct CompressParameters;
     impl Lz77Parameters for CompressParameters {
         const MAX_OFFSET: usize = 0x1FFF;
         const MAX_LENGTH: usize = 256;
+        const MAX_SHORT_OFFSET: usize = 0x100;
     }
 }
@@ -0,0 +1,272 @@
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+use core::{mem::size_of, ptr::read_unaligned};
+
+/// Signature shared by all implementations of [`match_length`].
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+type MatchLengthFn = unsafe fn(*const u8, *const u8, usize, usize) -> usize;
+
+/// Returns the number of leading bytes `a` and `b` have in common, given that the first `length`
+/// bytes are already known to match. The result is at most `max_length`.
+///
+/// Uses the widest vector instructions available: AVX2 or SSE2 on x86_64, and NEON on aarch64,
+/// with a scalar fallback elsewhere. On x86_64 with the `std` feature, AVX2 support is detected
+/// at runtime; otherwise the implementation is chosen at compile time, based on the enabled
+/// target features. Every implementation returns the same length.
+///
+/// # Parameters
+///
+/// - `a`: The first data to compare.
+/// - `b`: The second data to compare.
+/// - `length`: The number of leading bytes already known to match.
+/// - `max_length`: The maximum length to return.
+///
+/// # Safety
+///
+/// `a` and `b` must be valid for reads of `max_length` bytes, and `length` must not be greater
+/// than `max_length`.
+#[inline(always)]
+pub(crate) unsafe fn match_length(
+    a: *const u8,
+    b: *const u8,
+    length: usize,
+    max_length: usize,
+) -> usize {
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    {
+        // Most matches are short, so the first 16 bytes are checked inline,
+        // and the call to the selected implementation is only made for longer ones.
+        if length + 16 > max_length {
+            return match_length_scalar(a, b, length, max_length);
+        }
+
+        let equal = sse2_equal_mask(a.add(length), b.add(length));
+        if equal != 0xFFFF {
+            return length + equal.trailing_ones() as usize;
+        }
+
+        let selected = SELECTED.load(Ordering::Relaxed);
+        core::mem::transmute::<*mut (), MatchLengthFn>(selected)(a, b, length + 16, max_length)
+    }
+
+    #[cfg(all(target_arch = "x86_64", not(feature = "std"), target_feature = "avx2"))]
+    {
+        match_length_avx2(a, b, length, max_length)
+    }
+
+    #[cfg(all(
+        target_arch = "x86_64",
+        not(feature = "std"),
+        not(target_feature = "avx2")
+    ))]
+    {
+        match_length_sse2(a, b, length, max_length)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        match_length_neon(a, b, length, max_length)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        match_length_scalar(a, b, length, max_length)
+    }
+}
+
+/// The implementation used by [`match_length`]. Starts out as [`detect`], which replaces itself
+/// with the best implementation the CPU supports on first use.
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+static SELECTED: AtomicPtr<()> = AtomicPtr::new(detect as *mut ());
+
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+unsafe fn detect(a: *const u8, b: *const u8, length: usize, max_length: usize) -> usize {
+    let selected: MatchLengthFn = if std::is_x86_feature_detected!("avx2") {
+        match_length_avx2
+    } else {
+        match_length_sse2
+    };
+
+    SELECTED.store(selected as *mut (), Ordering::Relaxed);
+    selected(a, b, length, max_length)
+}
+
+/// Compares `usize` at a time, then byte by byte.
+#[inline(always)]
+pub(crate) unsafe fn match_length_scalar(
+    a: *const u8,
+    b: *const u8,
+    mut length: usize,
+    max_length: usize,
+) -> usize {
+    while length + size_of::<usize>() <= max_length {
+        let diff = read_unaligned(a.add(length) as *const usize)
+            ^ read_unaligned(b.add(length) as *const usize);
+
+        if diff != 0 {
+            // The first differing byte is the lowest one in memory.
+            let diff_bits = if cfg!(target_endian = "little") {
+                diff.trailing_zeros()
+            } else {
+                diff.leading_zeros()
+            };
+
+            return length + (diff_bits / 8) as usize;
+        }
+
+        length += size_of::<usize>();
+    }
+
+    while length < max_length && *a.add(length) == *b.add(length) {
+        length += 1;
+    }
+
+    length
+}
+
+/// Compares 16 bytes at a time. SSE2 is available on every x86_64 CPU.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn match_length_sse2(
+    a: *const u8,
+    b: *const u8,
+    mut length: usize,
+    max_length: usize,
+) -> usize {
+    while length + 16 <= max_length {
+        let equal = sse2_equal_mask(a.add(length), b.add(length));
+        if equal != 0xFFFF {
+            return length + equal.trailing_ones() as usize;
+        }
+
+        length += 16;
+    }
+
+    match_length_scalar(a, b, length, max_length)
+}
+
+/// Compares 16 bytes of `a` and `b`, returning one bit per byte, set if the bytes are equal.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+unsafe fn sse2_equal_mask(a: *const u8, b: *const u8) -> u32 {
+    use core::arch::x86_64::{__m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8};
+
+    let a_bytes = _mm_loadu_si128(a as *const __m128i);
+    let b_bytes = _mm_loadu_si128(b as *const __m128i);
+    _mm_movemask_epi8(_mm_cmpeq_epi8(a_bytes, b_bytes)) as u32
+}
+
+/// Compares 32 bytes at a time.
+#[cfg(all(target_arch = "x86_64", any(feature = "std", target_feature = "avx2")))]
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn match_length_avx2(
+    a: *const u8,
+    b: *const u8,
+    mut length: usize,
+    max_length: usize,
+) -> usize {
+    use core::arch::x86_64::{
+        __m256i, _mm256_cmpeq_epi8, _mm256_loadu_si256, _mm256_movemask_epi8,
+    };
+
+    while length + 32 <= max_length {
+        let a_bytes = _mm256_loadu_si256(a.add(length) as *const __m256i);
+        let b_bytes = _mm256_loadu_si256(b.add(length) as *const __m256i);
+
+        // One bit per byte, set if the bytes are equal.
+        let equal = _mm256_movemask_epi8(_mm256_cmpeq_epi8(a_bytes, b_bytes)) as u32;
+        if equal != u32::MAX {
+            return length + equal.trailing_ones() as usize;
+        }
+
+        length += 32;
+    }
+
+    match_length_sse2(a, b, length, max_length)
+}
+
+/// Compares 16 bytes at a time.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn match_length_neon(
+    a: *const u8,
+    b: *const u8,
+    mut length: usize,
+    max_length: usize,
+) -> usize {
+    use core::arch::aarch64::{
+        vceqq_u8, vget_lane_u64, vld1q_u8, vreinterpret_u64_u8, vreinterpretq_u16_u8, vshrn_n_u16,
+    };
+
+    while length + 16 <= max_length {
+        let equal = vceqq_u8(vld1q_u8(a.add(length)), vld1q_u8(b.add(length)));
+
+        // Narrow each byte of the comparison to 4 bits, giving a 64-bit mask.
+        let equal = vshrn_n_u16::<4>(vreinterpretq_u16_u8(equal));
+        let equal = vget_lane_u64::<0>(vreinterpret_u64_u8(equal));
+        if equal != u64::MAX {
+            return length + (equal.trailing_ones() / 4) as usize;
+        }
+
+        length += 16;
+    }
+
+    match_length_scalar(a, b, length, max_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    type Implementation = unsafe fn(*const u8, *const u8, usize, usize) -> usize;
+
+    /// Returns every implementation supported by the current CPU.
+    fn implementations() -> Vec<(&'static str, Implementation)> {
+        let mut result: Vec<(&'static str, Implementation)> = Vec::from([
+            ("scalar", match_length_scalar as _),
+            ("selected", match_length as _),
+        ]);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            result.push(("sse2", match_length_sse2 as _));
+            #[cfg(feature = "std")]
+            if std::is_x86_feature_detected!("avx2") {
+                result.push(("avx2", match_length_avx2 as _));
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        result.push(("neon", match_length_neon as _));
+
+        result
+    }
+
+    #[test]
+    fn all_implementations_match_byte_by_byte_comparison() {
+        let a: Vec<u8> = (0..300_u32).map(|x| (x * 7) as u8).collect();
+        for mismatch in 0..=a.len() {
+            let mut b = a.clone();
+            if let Some(byte) = b.get_mut(mismatch) {
+                *byte ^= 0x80;
+            }
+
+            for start in [0, 2, 4, 13] {
+                for max_length in [4, 15, 16, 31, 32, 33, 256, 300] {
+                    // The first `start` bytes must match.
+                    if start > max_length || start > mismatch {
+                        continue;
+                    }
+
+                    let expected = mismatch.min(max_length);
+                    for (name, implementation) in implementations() {
+                        let length =
+                            unsafe { implementation(a.as_ptr(), b.as_ptr(), start, max_length) };
+                        assert_eq!(length, expected, "{name} {mismatch} {start} {max_length}");
+                    }
+                }
+            }
+        }
+    }
+}
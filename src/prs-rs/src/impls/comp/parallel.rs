@@ -0,0 +1,146 @@
+use super::compress::{CompressWriter, CompressionOptions, MAX_OFFSET, WINDOW_SIZE};
+use super::compressor::PrsCompressor;
+use crate::impls::decomp::common::{read_byte, read_two_le, retrieve_control_bit};
+use crate::util::prs_calculate_max_compressed_size;
+use alloc::vec::Vec;
+use std::thread;
+
+/// Compresses `source` using up to `num_threads` threads, returning the compressed data in a new [`Vec`].
+///
+/// The input is split into segments (a multiple of `WINDOW_SIZE` in length), which are compressed
+/// independently, each using the preceding [`MAX_OFFSET`] bytes as look-behind. The resulting streams
+/// are then re-bitpacked into a single PRS stream.
+///
+/// # Parameters
+///
+/// - `source`: The data to compress.
+/// - `options`: Options which control how the data is compressed.
+/// - `num_threads`: Maximum number of threads to use. 0 is treated as 1.
+///
+/// # Remarks
+///
+/// Matches can't extend past the end of a segment, so the output may be slightly larger than
+/// that of [`crate::comp::prs_compress_with_options`]. With a single segment, the output is identical.
+pub fn prs_compress_parallel(
+    source: &[u8],
+    options: &CompressionOptions,
+    num_threads: usize,
+) -> Vec<u8> {
+    let segment_len = source
+        .len()
+        .div_ceil(num_threads.max(1))
+        .div_ceil(WINDOW_SIZE)
+        .max(1)
+        * WINDOW_SIZE;
+
+    let segments: Vec<Vec<u8>> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..source.len())
+            .step_by(segment_len)
+            .map(|start| {
+                let end = (start + segment_len).min(source.len());
+                let look_behind = start.min(MAX_OFFSET);
+                let data = &source[start - look_behind..end];
+                scope.spawn(move || {
+                    PrsCompressor::with_options(*options).compress_range_to_vec(data, look_behind)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+
+    // The segments are re-bitpacked into a single stream, so control bytes aren't wasted at segment
+    // boundaries. Copies cost fewer bits per byte than literals, so this is still the upper bound.
+    let mut dest = Vec::with_capacity(prs_calculate_max_compressed_size(source.len()));
+    unsafe {
+        let orig_dest = dest.as_mut_ptr() as usize;
+        let mut writer = CompressWriter::new(dest.as_mut_ptr());
+        for segment in &segments {
+            append_stream(&mut writer, segment);
+        }
+
+        writer.write_terminator();
+        dest.set_len(writer.dest as usize - orig_dest);
+    }
+
+    dest
+}
+
+/// Appends all opcodes of the PRS stream in `source`, except the terminator, to `writer`.
+///
+/// Control bits can't be copied byte by byte, since the stream being appended to may have
+/// a partially filled control byte. Instead, each opcode is read and written again, with control
+/// bits and data bytes in the same order the decompressor reads them.
+///
+/// # Safety
+///
+/// `source` must be a valid PRS stream, and `writer` must have enough space for it.
+unsafe fn append_stream(writer: &mut CompressWriter, source: &[u8]) {
+    let mut source = source.as_ptr();
+    let mut control_byte = 0;
+    let mut current_bit_position = 8;
+
+    loop {
+        // Literal
+        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
+            writer.write_control_bit(1);
+            writer.write_byte(read_byte(&mut source) as u8);
+            continue;
+        }
+
+        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
+            // Long copy, stop at end of stream.
+            let ofs_bytes = read_two_le(&mut source);
+            if ofs_bytes == 0 {
+                return;
+            }
+
+            writer.write_control_bit(0);
+            writer.write_control_bit(1);
+            writer.write_byte(ofs_bytes as u8);
+            writer.write_byte((ofs_bytes >> 8) as u8);
+
+            // Length didn't fit in the offset, and is stored in the next byte.
+            if ofs_bytes & 0b111 == 0 {
+                writer.write_byte(read_byte(&mut source) as u8);
+            }
+        } else {
+            // Short copy
+            let high =
+                retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source);
+            let low =
+                retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source);
+
+            writer.write_control_bit(0);
+            writer.write_control_bit(0);
+            writer.write_control_bit(high as u8);
+            writer.write_control_bit(low as u8);
+            writer.write_byte(read_byte(&mut source) as u8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comp::prs_compress;
+
+    #[test]
+    fn append_stream_reproduces_stream() {
+        let data = b"abcabcabcabcabcdefghijklmnopqrstuvwxyzabcdefghijklmnopqrstuvwxyz";
+        let compressed = prs_compress(data);
+
+        let mut dest = Vec::with_capacity(compressed.len());
+        unsafe {
+            let mut writer = CompressWriter::new(dest.as_mut_ptr());
+            append_stream(&mut writer, &compressed);
+            writer.write_terminator();
+            dest.set_len(writer.dest as usize - dest.as_ptr() as usize);
+        }
+
+        assert_eq!(compressed, dest);
+    }
+}
@@ -0,0 +1,58 @@
+use super::compress::CompressionOptions;
+use super::compressor::PrsCompressor;
+use crate::error::PrsError;
+use crate::prelude::{AllocError, Allocator, Layout};
+use crate::util::prs_calculate_max_compressed_size;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// [`Allocator`] for memory provided by the caller, see [`prs_compress_with_scratch`].
+///
+/// Never allocates, and freeing memory does nothing, as the memory is owned by the caller.
+#[derive(Clone, Copy)]
+pub(crate) struct NoAlloc;
+
+unsafe impl Allocator for NoAlloc {
+    fn allocate(&self, _layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        Err(AllocError)
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
+}
+
+/// Returns the number of bytes of scratch memory [`prs_compress_with_scratch`] needs,
+/// see [`crate::comp::prs_compress_scratch_size`].
+pub fn prs_compress_scratch_size(src_len: usize, options: &CompressionOptions) -> usize {
+    PrsCompressor::scratch_size(src_len, options)
+}
+
+/// Compresses `src` into `dest`, using `scratch` for the dictionary and other temporary buffers,
+/// see [`crate::comp::prs_compress_with_scratch`].
+pub fn prs_compress_with_scratch(
+    src: &[u8],
+    dest: &mut [MaybeUninit<u8>],
+    options: &CompressionOptions,
+    scratch: &mut [u8],
+) -> Result<usize, PrsError> {
+    let required = prs_compress_scratch_size(src.len(), options);
+    if scratch.len() < required {
+        return Err(PrsError::ScratchTooSmall { required });
+    }
+
+    let required = prs_calculate_max_compressed_size(src.len());
+    if dest.len() < required {
+        return Err(PrsError::BufferTooSmall { required });
+    }
+
+    unsafe {
+        // The compressor is dropped before `scratch` is released.
+        let mut compressor = PrsCompressor::from_scratch(*options, src.len(), scratch);
+        let compressed_len = compressor
+            .try_compress_unsafe(src.as_ptr(), src.len(), dest.as_mut_ptr() as *mut u8)
+            .unwrap_or_else(|_| {
+                // Every buffer the level needs was placed in `scratch`, whose size was checked above.
+                unreachable!("compressing with scratch memory tried to allocate")
+            });
+        Ok(compressed_len)
+    }
+}
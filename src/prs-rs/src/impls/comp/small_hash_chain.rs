@@ -0,0 +1,166 @@
+use super::compress::SMALL_INPUT_THRESHOLD;
+use super::lz77_matcher::{Lz77Match, Lz77Parameters};
+use super::match_length::match_length as extend_match;
+use core::ptr::read_unaligned;
+
+/// Number of buckets the 2 byte keys are hashed into. Must be a power of 2.
+const NUM_BUCKETS: usize = 1024;
+
+/// Hash chain for inputs shorter than [`SMALL_INPUT_THRESHOLD`], used by
+/// [`super::compress::compress_range_small`].
+///
+/// Works like [`super::hash_chain::HashChain`], but is small enough to live on the stack, so setting
+/// it up costs next to nothing. Keys are hashed into [`NUM_BUCKETS`] buckets, and candidates with
+/// a different key are skipped. Every candidate with the same key is checked, nearest first, so this
+/// finds the same matches as [`super::comp_dict::CompDict`].
+pub(crate) struct SmallHashChain {
+    /// Last position (+ 1) in each bucket. 0 if the bucket is empty.
+    head: [u16; NUM_BUCKETS],
+    /// Previous position (+ 1) in the same bucket as the position at each index.
+    /// 0 if there is none.
+    prev: [u16; SMALL_INPUT_THRESHOLD],
+}
+
+impl SmallHashChain {
+    /// Creates a new, empty [`SmallHashChain`].
+    pub(crate) fn new() -> Self {
+        Self {
+            head: [0; NUM_BUCKETS],
+            prev: [0; SMALL_INPUT_THRESHOLD],
+        }
+    }
+
+    /// Returns the bucket of the given 2 byte key.
+    #[inline(always)]
+    fn bucket(key: u16) -> usize {
+        ((key as u32).wrapping_mul(0x9E37_79B1) >> (32 - NUM_BUCKETS.trailing_zeros())) as usize
+    }
+
+    /// Adds the 2 byte key at `source_index` to the chain.
+    ///
+    /// # Safety
+    ///
+    /// `source_index + 1` must be within bounds of `source_ptr`, and less than [`SMALL_INPUT_THRESHOLD`].
+    /// Positions must be inserted in ascending order.
+    #[inline(always)]
+    pub(crate) unsafe fn insert(&mut self, source_ptr: *const u8, source_index: usize) {
+        let bucket = Self::bucket(read_unaligned(source_ptr.add(source_index) as *const u16));
+        *self.prev.get_unchecked_mut(source_index) = *self.head.get_unchecked(bucket);
+        *self.head.get_unchecked_mut(bucket) = (source_index + 1) as u16;
+    }
+
+    /// Returns the longest match for the data at `source_index`, preferring the nearest on ties.
+    ///
+    /// # Parameters
+    ///
+    /// - `source_ptr`: The data where the match is to be searched.
+    /// - `source_len`: The length of the data.
+    /// - `source_index`: The index of the current byte in the source.
+    ///
+    /// # Safety
+    ///
+    /// `source_index + 1` must be less than `source_len`, and all positions before
+    /// `source_index` must have been inserted.
+    #[inline(always)]
+    pub(crate) unsafe fn find_longest_match<P: Lz77Parameters>(
+        &self,
+        source_ptr: *const u8,
+        source_len: usize,
+        source_index: usize,
+    ) -> Lz77Match {
+        let mut best_match = Lz77Match {
+            offset: 0,
+            length: 0,
+        };
+
+        let key = read_unaligned(source_ptr.add(source_index) as *const u16);
+        let max_match_length = P::MAX_LENGTH.min(source_len - source_index);
+        let mut candidate = *self.head.get_unchecked(Self::bucket(key)) as usize;
+
+        // Stop at the end of the chain, or once out of range.
+        while candidate != 0 && source_index - (candidate - 1) <= P::MAX_OFFSET {
+            let match_offset = candidate - 1;
+            candidate = *self.prev.get_unchecked(match_offset) as usize;
+
+            // Skip keys which only share the bucket.
+            if read_unaligned(source_ptr.add(match_offset) as *const u16) != key {
+                continue;
+            }
+
+            // We start having matched 2.
+            let match_length = extend_match(
+                source_ptr.add(match_offset),
+                source_ptr.add(source_index),
+                2,
+                max_match_length,
+            );
+
+            // Update the best match if this match is longer
+            if match_length > best_match.length {
+                best_match.length = match_length;
+                best_match.offset = match_offset as isize - source_index as isize;
+
+                if match_length == max_match_length {
+                    break;
+                }
+            }
+        }
+
+        best_match
+    }
+}
+
+// Positions (+ 1) must fit in a u16.
+const _: () = assert!(NUM_BUCKETS.is_power_of_two() && SMALL_INPUT_THRESHOLD <= u16::MAX as usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CompressParameters;
+    impl Lz77Parameters for CompressParameters {
+        const MAX_OFFSET: usize = 0x1FFF;
+        const MAX_LENGTH: usize = 256;
+        const MAX_SHORT_OFFSET: usize = 0x100;
+    }
+
+    #[test]
+    fn finds_nearest_longest_match() {
+        let data = b"abcabcabcabcabc";
+        let mut chain = SmallHashChain::new();
+        unsafe {
+            for x in 0..3 {
+                chain.insert(data.as_ptr(), x);
+            }
+
+            let result =
+                chain.find_longest_match::<CompressParameters>(data.as_ptr(), data.len(), 3);
+            assert_eq!(result.length, 12);
+            assert_eq!(result.offset, -3);
+        }
+    }
+
+    #[test]
+    fn skips_keys_in_same_bucket() {
+        // Find a key which shares a bucket with "ab", and put it nearer than "ab".
+        let key = u16::from_le_bytes(*b"ab");
+        let other = (0..=u16::MAX)
+            .find(|&x| x != key && SmallHashChain::bucket(x) == SmallHashChain::bucket(key))
+            .unwrap()
+            .to_le_bytes();
+
+        let mut data = *b"abcd__abx";
+        data[4..6].copy_from_slice(&other);
+        let mut chain = SmallHashChain::new();
+        unsafe {
+            for x in 0..6 {
+                chain.insert(data.as_ptr(), x);
+            }
+
+            let result =
+                chain.find_longest_match::<CompressParameters>(data.as_ptr(), data.len(), 6);
+            assert_eq!(result.length, 2);
+            assert_eq!(result.offset, -6);
+        }
+    }
+}
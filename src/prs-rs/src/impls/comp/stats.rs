@@ -0,0 +1,179 @@
+use crate::error::PrsError;
+use crate::impls::decomp::tokens::{Token, Tokens};
+use core::time::Duration;
+
+/// Number of entries in [`CompressionStats::length_histogram`]; one per possible copy length (0 is unused).
+pub const LENGTH_HISTOGRAM_LEN: usize = 257;
+
+/// Number of entries in [`CompressionStats::offset_histogram`]; enough for distances up to 0x2000.
+pub const OFFSET_HISTOGRAM_LEN: usize = 14;
+
+/// Number of opcodes of one kind, and the number of decompressed bytes they produce.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpcodeStats {
+    /// Number of opcodes.
+    pub count: usize,
+    /// Number of decompressed bytes produced by the opcodes.
+    pub bytes: usize,
+}
+
+impl OpcodeStats {
+    fn add(&mut self, bytes: usize) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Breakdown of the opcodes in a PRS stream, and of where time was spent compressing it.
+///
+/// Returned by [`crate::comp::prs_compress_with_stats`] and [`crate::comp::PrsCompressor::compress_with_stats`].
+/// Useful for figuring out why some data compresses poorly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Literals (`1` opcode).
+    pub literals: OpcodeStats,
+    /// Short copies (`00` opcode); length 2 - 5, up to 0x100 bytes back.
+    pub short_copies: OpcodeStats,
+    /// Long copies (`01` opcode) with the length packed together with the offset; length 3 - 9.
+    pub long_copies_small: OpcodeStats,
+    /// Long copies (`01` opcode) with the length in an extra byte; length 1 - 256.
+    pub long_copies_large: OpcodeStats,
+    /// Number of copies of each length, indexed by length.
+    pub length_histogram: [usize; LENGTH_HISTOGRAM_LEN],
+    /// Number of copies by distance; entry `i` counts copies from `2^i` to `2^(i + 1) - 1` bytes back.
+    pub offset_histogram: [usize; OFFSET_HISTOGRAM_LEN],
+    /// Time spent building the compression dictionary, for each window of data.
+    /// Zero for levels which don't use the dictionary, or without the `std` feature.
+    pub dict_init_time: Duration,
+    /// Time spent searching for (and encoding) matches, i.e. all compression time except
+    /// [`CompressionStats::dict_init_time`]. Zero without the `std` feature.
+    pub match_search_time: Duration,
+}
+
+impl Default for CompressionStats {
+    fn default() -> Self {
+        Self {
+            literals: OpcodeStats::default(),
+            short_copies: OpcodeStats::default(),
+            long_copies_small: OpcodeStats::default(),
+            long_copies_large: OpcodeStats::default(),
+            length_histogram: [0; LENGTH_HISTOGRAM_LEN],
+            offset_histogram: [0; OFFSET_HISTOGRAM_LEN],
+            dict_init_time: Duration::ZERO,
+            match_search_time: Duration::ZERO,
+        }
+    }
+}
+
+impl CompressionStats {
+    /// Collects the opcode statistics of existing PRS compressed data, e.g. files made by other tools.
+    /// Times are left at zero.
+    ///
+    /// # Parameters
+    ///
+    /// - `src`: The compressed data.
+    ///
+    /// # Returns
+    ///
+    /// The statistics, or an error if `src` is not a complete PRS stream.
+    pub fn from_compressed(src: &[u8]) -> Result<Self, PrsError> {
+        let mut stats = Self::default();
+        for info in Tokens::new(src) {
+            stats.add_token(info?.token);
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the combined statistics of all kinds of copies.
+    pub fn copies(&self) -> OpcodeStats {
+        OpcodeStats {
+            count: self.short_copies.count
+                + self.long_copies_small.count
+                + self.long_copies_large.count,
+            bytes: self.short_copies.bytes
+                + self.long_copies_small.bytes
+                + self.long_copies_large.bytes,
+        }
+    }
+
+    /// Returns the average length of a copy, or 0 if there are none.
+    pub fn average_match_length(&self) -> f64 {
+        let copies = self.copies();
+        if copies.count == 0 {
+            return 0.0;
+        }
+
+        copies.bytes as f64 / copies.count as f64
+    }
+
+    fn add_token(&mut self, token: Token) {
+        let (offset, len) = match token {
+            Token::Literal(_) => {
+                self.literals.add(1);
+                return;
+            }
+            Token::ShortCopy { offset, len } => {
+                self.short_copies.add(len);
+                (offset, len)
+            }
+            Token::LongCopy {
+                offset,
+                len,
+                extended,
+            } => {
+                if extended {
+                    self.long_copies_large.add(len);
+                } else {
+                    self.long_copies_small.add(len);
+                }
+
+                (offset, len)
+            }
+            Token::End => return,
+        };
+
+        self.length_histogram[len] += 1;
+        self.offset_histogram[offset.unsigned_abs().ilog2() as usize] += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_opcode() {
+        // Literal 'A', literal 'B', short copy (len 2, offset -2), long copy (len 3, offset -4),
+        // then a second control byte, long copy (len 5, extended, offset -4), end.
+        let data: &[u8] = &[
+            0b1000_0011,
+            0x41,
+            0x42,
+            0xFE,
+            0xE1,
+            0xFF,
+            0b0000_1010,
+            0xE0,
+            0xFF,
+            0x04,
+            0x00,
+            0x00,
+        ];
+
+        let stats = CompressionStats::from_compressed(data).unwrap();
+        assert_eq!(stats.literals, OpcodeStats { count: 2, bytes: 2 });
+        assert_eq!(stats.short_copies, OpcodeStats { count: 1, bytes: 2 });
+        assert_eq!(stats.long_copies_small, OpcodeStats { count: 1, bytes: 3 });
+        assert_eq!(stats.long_copies_large, OpcodeStats { count: 1, bytes: 5 });
+        assert_eq!(
+            stats.copies(),
+            OpcodeStats {
+                count: 3,
+                bytes: 10
+            }
+        );
+        assert_eq!(stats.length_histogram[2..6], [1, 1, 0, 1]);
+        assert_eq!(stats.offset_histogram[..3], [0, 1, 2]);
+    }
+}
@@ -0,0 +1,240 @@
+use super::compress::{
+    write_long_copy_large, write_long_copy_small, write_short_copy, CompressWriter,
+    COPY_MAX_LENGTH, LONG_COPY_MIN_LEN, LONG_COPY_SMALL_MAX_LEN, MAX_OFFSET, SHORT_COPY_MAX_LEN,
+    SHORT_COPY_MAX_OFFSET, SHORT_COPY_MIN_LEN,
+};
+use super::lz77_matcher::Lz77Match;
+use crate::error::PrsError;
+use crate::impls::decomp::tokens::Token;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Max number of bytes a single opcode can add to the output; a new control byte + 3 data bytes.
+const MAX_OPCODE_SIZE: usize = 4;
+
+/// Encodes a caller-provided sequence of tokens into a PRS stream.
+///
+/// This takes care of the bit packing, so custom match finders (parsers) can produce PRS data
+/// without reimplementing it. Each token is validated before being written, so the output is
+/// always a valid PRS stream.
+///
+/// # Example
+///
+/// ```
+/// use prs_rs::comp::PrsWriter;
+/// use prs_rs::decomp::prs_decompress_to_vec;
+///
+/// let mut writer = PrsWriter::new();
+/// writer.write_literal(b'a');
+/// writer.write_literal(b'b');
+/// writer.write_short_copy(-2, 4).unwrap();
+/// let compressed = writer.finish();
+/// assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), b"ababab");
+/// ```
+pub struct PrsWriter {
+    /// Compressed data written so far.
+    output: Vec<u8>,
+    /// Offset of the control byte currently being filled in `output`.
+    control_byte_ofs: usize,
+    /// The current bit position in the control byte.
+    control_bit_position: usize,
+    /// Length of the data the written tokens decompress to.
+    decompressed_len: usize,
+}
+
+impl Default for PrsWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrsWriter {
+    /// Creates a new writer, with an empty stream.
+    pub fn new() -> Self {
+        Self {
+            output: vec![0], // first control byte
+            control_byte_ofs: 0,
+            control_bit_position: 0,
+            decompressed_len: 0,
+        }
+    }
+
+    /// Returns the length of the data the tokens written so far decompress to.
+    pub fn decompressed_len(&self) -> usize {
+        self.decompressed_len
+    }
+
+    /// Writes a literal (`1` opcode), which is output as-is.
+    pub fn write_literal(&mut self, byte: u8) {
+        unsafe {
+            self.write(|writer| {
+                writer.write_control_bit(1);
+                writer.write_byte(byte);
+            })
+        };
+        self.decompressed_len += 1;
+    }
+
+    /// Writes a short copy (`00` opcode).
+    ///
+    /// # Parameters
+    ///
+    /// - `offset`: Offset of the data to copy, as a negative number; -1 to -256.
+    /// - `len`: Number of bytes to copy; 2 to 5.
+    ///
+    /// # Returns
+    ///
+    /// [`PrsError::InvalidToken`] if either parameter is out of range, or the copy references
+    /// data before the start of the output.
+    pub fn write_short_copy(&mut self, offset: isize, len: usize) -> Result<(), PrsError> {
+        self.validate_copy(
+            offset,
+            len,
+            SHORT_COPY_MAX_OFFSET as usize,
+            SHORT_COPY_MIN_LEN,
+            SHORT_COPY_MAX_LEN,
+        )?;
+
+        let result = Lz77Match {
+            offset,
+            length: len,
+        };
+        unsafe {
+            self.write(|writer| {
+                write_short_copy(
+                    &mut writer.dest,
+                    &result,
+                    &mut writer.control_bit_position,
+                    &mut writer.control_byte_ptr,
+                )
+            })
+        };
+        self.decompressed_len += len;
+        Ok(())
+    }
+
+    /// Writes a long copy (`01` opcode).
+    ///
+    /// # Parameters
+    ///
+    /// - `offset`: Offset of the data to copy, as a negative number; -1 to -0x2000, or -1 to -0x1FFF
+    ///   if `extended` (since an extended copy with an offset of -0x2000 encodes the terminator).
+    /// - `len`: Number of bytes to copy; 3 to 9, or 1 to 256 if `extended`.
+    /// - `extended`: Stores the length in an extra byte, rather than together with the offset.
+    ///
+    /// # Returns
+    ///
+    /// [`PrsError::InvalidToken`] if either parameter is out of range, or the copy references
+    /// data before the start of the output.
+    pub fn write_long_copy(
+        &mut self,
+        offset: isize,
+        len: usize,
+        extended: bool,
+    ) -> Result<(), PrsError> {
+        // The packed offset is 0 at -0x2000; only the length keeps that from being the terminator.
+        let (max_offset, min_len, max_len) = if extended {
+            (MAX_OFFSET, 1, COPY_MAX_LENGTH as usize)
+        } else {
+            (MAX_OFFSET + 1, LONG_COPY_MIN_LEN, LONG_COPY_SMALL_MAX_LEN)
+        };
+        self.validate_copy(offset, len, max_offset, min_len, max_len)?;
+
+        let result = Lz77Match {
+            offset,
+            length: len,
+        };
+        unsafe {
+            self.write(|writer| {
+                let write_long_copy = if extended {
+                    write_long_copy_large
+                } else {
+                    write_long_copy_small
+                };
+
+                write_long_copy(
+                    &mut writer.dest,
+                    &result,
+                    &mut writer.control_bit_position,
+                    &mut writer.control_byte_ptr,
+                )
+            })
+        };
+        self.decompressed_len += len;
+        Ok(())
+    }
+
+    /// Writes a [`Token`], e.g. one returned by [`crate::inspect::tokens`].
+    ///
+    /// # Returns
+    ///
+    /// [`PrsError::InvalidToken`] if the token can't be encoded.
+    /// [`Token::End`] is also rejected, since the terminator is written by [`PrsWriter::finish`].
+    pub fn write_token(&mut self, token: Token) -> Result<(), PrsError> {
+        match token {
+            Token::Literal(byte) => {
+                self.write_literal(byte);
+                Ok(())
+            }
+            Token::ShortCopy { offset, len } => self.write_short_copy(offset, len),
+            Token::LongCopy {
+                offset,
+                len,
+                extended,
+            } => self.write_long_copy(offset, len, extended),
+            Token::End => Err(self.invalid_token()),
+        }
+    }
+
+    /// Writes the terminator, and returns the finished PRS stream.
+    pub fn finish(mut self) -> Vec<u8> {
+        unsafe { self.write(|writer| writer.write_terminator()) };
+        self.output
+    }
+
+    /// Checks a copy is within the given ranges, and only references data which was already written.
+    fn validate_copy(
+        &self,
+        offset: isize,
+        len: usize,
+        max_offset: usize,
+        min_len: usize,
+        max_len: usize,
+    ) -> Result<(), PrsError> {
+        let distance = offset.unsigned_abs();
+        if offset >= 0
+            || distance > max_offset
+            || distance > self.decompressed_len
+            || len < min_len
+            || len > max_len
+        {
+            return Err(self.invalid_token());
+        }
+
+        Ok(())
+    }
+
+    fn invalid_token(&self) -> PrsError {
+        PrsError::InvalidToken {
+            decompressed_offset: self.decompressed_len,
+        }
+    }
+
+    /// Runs `write` with a [`CompressWriter`] pointing at the end of the output,
+    /// which may write up to [`MAX_OPCODE_SIZE`] bytes.
+    unsafe fn write(&mut self, write: impl FnOnce(&mut CompressWriter)) {
+        self.output.reserve(MAX_OPCODE_SIZE);
+
+        let base = self.output.as_mut_ptr();
+        let mut writer = CompressWriter {
+            dest: base.add(self.output.len()),
+            control_byte_ptr: base.add(self.control_byte_ofs),
+            control_bit_position: self.control_bit_position,
+        };
+        write(&mut writer);
+
+        self.output.set_len(writer.dest as usize - base as usize);
+        self.control_byte_ofs = writer.control_byte_ptr as usize - base as usize;
+        self.control_bit_position = writer.control_bit_position;
+    }
+}
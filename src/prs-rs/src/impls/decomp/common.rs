@@ -1,5 +1,65 @@
+use crate::error::PrsError;
+use core::convert::Infallible;
 use core::ptr::read_unaligned;
 
+/// Source of the control bits and data bytes of a PRS stream, read by [`super::tokens::decode_opcode`].
+///
+/// Implemented with and without bounds checks, so all decoders share the same opcode parsing.
+pub(crate) trait OpcodeSource {
+    /// Error returned if the stream ends early.
+    type Error;
+
+    fn read_control_bit(&mut self) -> Result<usize, Self::Error>;
+    fn read_byte(&mut self) -> Result<usize, Self::Error>;
+    fn read_two_le(&mut self) -> Result<usize, Self::Error>;
+}
+
+/// [`OpcodeSource`] reading from a pointer, without bounds checks.
+pub(crate) struct UncheckedSource {
+    source: *const u8,
+    control_byte: usize,
+    current_bit_position: usize,
+}
+
+impl UncheckedSource {
+    /// # Safety
+    ///
+    /// `source` must point to a valid PRS stream. It is read up to and including the terminator.
+    #[inline(always)]
+    pub(crate) unsafe fn new(source: *const u8) -> Self {
+        Self {
+            source,
+            control_byte: 0,
+            current_bit_position: 8, // control byte is read on first use
+        }
+    }
+}
+
+impl OpcodeSource for UncheckedSource {
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn read_control_bit(&mut self) -> Result<usize, Infallible> {
+        Ok(unsafe {
+            retrieve_control_bit(
+                &mut self.control_byte,
+                &mut self.current_bit_position,
+                &mut self.source,
+            )
+        })
+    }
+
+    #[inline(always)]
+    fn read_byte(&mut self) -> Result<usize, Infallible> {
+        Ok(unsafe { read_byte(&mut self.source) })
+    }
+
+    #[inline(always)]
+    fn read_two_le(&mut self) -> Result<usize, Infallible> {
+        Ok(unsafe { read_two_le(&mut self.source) })
+    }
+}
+
 #[inline]
 pub(crate) unsafe fn read_byte(source: &mut *const u8) -> usize {
     let byte = *source;
@@ -14,11 +74,6 @@ pub(crate) unsafe fn read_two_le(source: &mut *const u8) -> usize {
     u16::from_le(bytes) as usize
 }
 
-#[inline]
-pub(crate) unsafe fn advance_byte(source: &mut *const u8) {
-    *source = source.add(1);
-}
-
 #[inline]
 pub(crate) unsafe fn retrieve_control_bit(
     control_byte: &mut usize,
@@ -38,3 +93,51 @@ pub(crate) unsafe fn retrieve_control_bit(
 
     return_value
 }
+
+/// Bounds checked variant of [`read_byte`], reading from `source` at `*pos`.
+#[inline]
+pub(crate) fn try_read_byte(source: &[u8], pos: &mut usize) -> Result<usize, PrsError> {
+    match source.get(*pos) {
+        Some(&byte) => {
+            *pos += 1;
+            Ok(byte as usize)
+        }
+        None => Err(PrsError::UnexpectedEndOfInput {
+            input_offset: source.len(),
+        }),
+    }
+}
+
+/// Bounds checked variant of [`read_two_le`], reading from `source` at `*pos`.
+#[inline]
+pub(crate) fn try_read_two_le(source: &[u8], pos: &mut usize) -> Result<usize, PrsError> {
+    match source.get(*pos..*pos + 2) {
+        Some(bytes) => {
+            *pos += 2;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        }
+        None => Err(PrsError::UnexpectedEndOfInput {
+            input_offset: source.len(),
+        }),
+    }
+}
+
+/// Bounds checked variant of [`retrieve_control_bit`], reading from `source` at `*pos`.
+#[inline]
+pub(crate) fn try_retrieve_control_bit(
+    control_byte: &mut usize,
+    current_bit_position: &mut usize,
+    source: &[u8],
+    pos: &mut usize,
+) -> Result<usize, PrsError> {
+    if *current_bit_position >= 8 {
+        *control_byte = try_read_byte(source, pos)?;
+        *current_bit_position = 0;
+    }
+
+    let return_value = *control_byte & 0x01;
+    *control_byte >>= 1;
+    *current_bit_position += 1;
+
+    Ok(return_value)
+}
@@ -0,0 +1,155 @@
+use super::incremental::{DecodeStatus, PrsIncrementalDecoder};
+use crate::error::PrsError;
+use alloc::boxed::Box;
+use std::io::{self, ErrorKind, Read};
+
+/// Streaming PRS decompressor, which decompresses data from an underlying [`Read`] as it is read.
+///
+/// Only the last 0x1FFF bytes of decompressed data (the max distance of a copy) are kept in memory,
+/// so the compressed data does not need to be buffered up front.
+///
+/// This is a thin wrapper around [`PrsIncrementalDecoder`].
+///
+/// # Remarks
+///
+/// The decoder reads the underlying reader one byte at a time, and never reads past the end of
+/// the PRS stream. This means that after the stream is fully decoded, [`PrsDecoder::into_inner`]
+/// returns the reader positioned directly after the compressed data.
+///
+/// If the underlying reader is unbuffered (e.g. [`std::fs::File`] or a socket), wrap it in a
+/// [`std::io::BufReader`] first.
+///
+/// Malformed data is reported as an [`io::Error`] wrapping a [`PrsError`].
+pub struct PrsDecoder<R: Read> {
+    inner: R,
+    decoder: Box<PrsIncrementalDecoder>,
+    /// Error encountered after some data was already output, returned on next read.
+    pending_error: Option<io::Error>,
+}
+
+impl<R: Read> PrsDecoder<R> {
+    /// Creates a new decoder which decompresses the PRS stream read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            decoder: Box::new(PrsIncrementalDecoder::new()),
+            pending_error: None,
+        }
+    }
+
+    /// Returns `true` once the terminator of the PRS stream has been read.
+    pub fn is_finished(&self) -> bool {
+        self.decoder.is_finished()
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    ///
+    /// Reading from the underlying reader mid-stream will corrupt the decompressed output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this decoder, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Feeds the next byte of the underlying reader to the decoder.
+    fn decode_next_byte(&mut self) -> io::Result<()> {
+        let mut byte = [0_u8; 1];
+        if let Err(e) = self.inner.read_exact(&mut byte) {
+            return Err(match e.kind() {
+                ErrorKind::UnexpectedEof => io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    PrsError::UnexpectedEndOfInput {
+                        input_offset: self.decoder.total_in(),
+                    },
+                ),
+                _ => e,
+            });
+        }
+
+        let (consumed, _) = self.decoder.decode(&byte).map_err(invalid_data)?;
+        debug_assert_eq!(consumed, 1);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for PrsDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.pending_error.take() {
+            return Err(e);
+        }
+
+        let mut written = 0;
+        loop {
+            written += self.decoder.read_output(&mut buf[written..]);
+            if written == buf.len() {
+                break;
+            }
+
+            // The ring buffer is now empty, continue any pending copy.
+            let status = match self.decoder.decode(&[]) {
+                Ok((_, status)) => status,
+                Err(e) => Err(invalid_data(e))?,
+            };
+
+            let result = match status {
+                DecodeStatus::OutputFull => continue,
+                DecodeStatus::Finished if self.decoder.output().is_empty() => break,
+                DecodeStatus::Finished => continue,
+                DecodeStatus::NeedInput => self.decode_next_byte(),
+            };
+
+            // Don't lose already decompressed data if decoding fails;
+            // return it now, and the error on the next call.
+            if let Err(e) = result {
+                if written == 0 {
+                    return Err(e);
+                }
+
+                self.pending_error = Some(e);
+                break;
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+fn invalid_data(e: PrsError) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn decoder_detects_back_reference_before_start() {
+        // 1 literal, then short copy (offset -2), which points before start of output.
+        let data: &[u8] = &[0b0000_0001, 0x41, 0xFE];
+        let mut decoder = PrsDecoder::new(data);
+        let mut result = Vec::new();
+        let err = decoder.read_to_end(&mut result).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(result, b"A");
+    }
+
+    #[test]
+    fn decoder_detects_truncated_input() {
+        // 2 literals, then no terminator.
+        let data: &[u8] = &[0b0000_0011, 0x41, 0x42];
+        let mut decoder = PrsDecoder::new(data);
+        let mut result = Vec::new();
+        let err = decoder.read_to_end(&mut result).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(result, b"AB");
+    }
+}
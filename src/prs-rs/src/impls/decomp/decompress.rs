@@ -1,99 +1,201 @@
-use super::common::{read_byte, read_two_le, retrieve_control_bit};
+use super::common::UncheckedSource;
+use super::tokens::{decode_opcode_unchecked, Token, TokenReader, Tokens};
+use crate::error::PrsError;
 
-pub(crate) unsafe fn prs_decompress(mut source: *const u8, mut dest: *mut u8) -> usize {
-    let mut control_byte = read_byte(&mut source);
-    let mut current_bit_position = 0;
+pub(crate) unsafe fn prs_decompress(source: *const u8, mut dest: *mut u8) -> usize {
+    let mut source = UncheckedSource::new(source);
     let mut file_size = 0;
 
     loop {
-        // Test for Direct Byte (Opcode 1)
-        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
-            *dest = read_byte(&mut source) as u8;
-            dest = dest.add(1);
-            file_size += 1;
-            continue;
+        match decode_opcode_unchecked(&mut source) {
+            Token::Literal(byte) => {
+                *dest = byte;
+                dest = dest.add(1);
+                file_size += 1;
+            }
+            // Separate copies for each opcode, so the short one can be unrolled.
+            Token::ShortCopy { offset, len } => {
+                copy_unchecked(&mut dest, &mut file_size, offset, len)
+            }
+            Token::LongCopy { offset, len, .. } => {
+                copy_unchecked(&mut dest, &mut file_size, offset, len)
+            }
+            Token::End => break,
+        }
+    }
+
+    file_size
+}
+
+/// Bounds checked counterpart of [`prs_decompress`].
+///
+/// Never reads outside of `source` or writes outside of `dest`; malformed input is
+/// reported as a [`PrsError`] instead.
+pub(crate) fn prs_decompress_checked(source: &[u8], dest: &mut [u8]) -> Result<usize, PrsError> {
+    prs_decompress_checked_with_dictionary(&[], source, dest)
+}
+
+/// Same as [`prs_decompress_checked`], but copies may also reference data in `dictionary`,
+/// which is treated as if it came directly before `dest`.
+pub(crate) fn prs_decompress_checked_with_dictionary(
+    dictionary: &[u8],
+    source: &[u8],
+    dest: &mut [u8],
+) -> Result<usize, PrsError> {
+    let mut tokens = Tokens::new(source);
+    let mut file_size = 0;
+
+    loop {
+        let info = tokens.read_token()?;
+        let (offset, length) = match info.token {
+            Token::Literal(byte) => {
+                match dest.get_mut(file_size) {
+                    Some(out) => *out = byte,
+                    None => {
+                        return Err(PrsError::OutputOverflow {
+                            input_offset: info.data_offset,
+                        })
+                    }
+                }
+
+                file_size += 1;
+                continue;
+            }
+            Token::ShortCopy { offset, len } | Token::LongCopy { offset, len, .. } => (offset, len),
+            Token::End => break,
+        };
+
+        copy_checked(
+            dictionary,
+            dest,
+            &mut file_size,
+            offset,
+            length,
+            info.data_offset,
+        )?;
+    }
+
+    Ok(file_size)
+}
+
+/// Decompresses the PRS stream at `buf[compressed_start..]` into the start of `buf`.
+///
+/// Each opcode is read before its output is written, so the output may overwrite compressed data
+/// which was already read, but never data which was not; that fails with [`PrsError::InPlaceOverrun`].
+pub(crate) fn prs_decompress_in_place(
+    buf: &mut [u8],
+    compressed_start: usize,
+) -> Result<usize, PrsError> {
+    if compressed_start > buf.len() {
+        return Err(PrsError::UnexpectedEndOfInput { input_offset: 0 });
+    }
+
+    let mut reader = TokenReader::new();
+    let mut file_size = 0;
+
+    loop {
+        let info = reader.read_token(&buf[compressed_start..])?;
+
+        // Everything before the unread compressed data may be written to.
+        let unread_start = compressed_start + reader.position();
+        if file_size + info.token.decompressed_len() > unread_start {
+            return Err(PrsError::InPlaceOverrun {
+                input_offset: info.data_offset,
+            });
         }
 
-        // Opcode 1 failed, now testing for Opcode 0X
-        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
-            // Test for Opcode 01
-            // Append size of long copy, break if it's end of file.
-            if decode_long_copy(&mut source, &mut dest, &mut file_size) {
-                break;
+        match info.token {
+            Token::Literal(byte) => {
+                buf[file_size] = byte;
+                file_size += 1;
+            }
+            Token::ShortCopy { offset, len } | Token::LongCopy { offset, len, .. } => {
+                copy_checked(
+                    &[],
+                    &mut buf[..unread_start],
+                    &mut file_size,
+                    offset,
+                    len,
+                    info.data_offset,
+                )?;
             }
-        } else {
-            // Do Opcode 00
-            decode_short_copy(
-                &mut control_byte,
-                &mut current_bit_position,
-                &mut source,
-                &mut dest,
-                &mut file_size,
-            );
+            Token::End => break,
         }
     }
 
-    file_size
+    Ok(file_size)
+}
+
+/// Obtains the offset of a long copy from its packed 2 byte value. (negative i32, truncated to u16)
+/// We lost our negative sign when we originally wrote the offset, doing -0x2000 will restore it.
+#[inline(always)]
+pub(crate) fn long_copy_offset(ofs_bytes: usize) -> isize {
+    (ofs_bytes as isize >> 3) | -0x2000
+}
+
+/// Obtains the length of a long copy from its packed 2 byte value.
+/// Returns [`None`] if the length is stored in an extra byte that follows.
+#[inline(always)]
+pub(crate) fn long_copy_length(ofs_bytes: usize) -> Option<usize> {
+    let length = ofs_bytes & 0b111;
+    if length == 0 {
+        None // length: 1 - 256, in next byte
+    } else {
+        Some(length + 2) // length: 3 - 9
+    }
+}
+
+/// Obtains the offset of a short copy from its single byte. (negative i32, truncated to byte)
+/// We lost our sign when we originally wrote the offset, doing -0x100 will restore it.
+#[inline(always)]
+pub(crate) fn short_copy_offset(ofs_byte: usize) -> isize {
+    ofs_byte as isize | -0x100
 }
 
+/// Copies `length` bytes from `offset` bytes behind the end of the decompressed data,
+/// validating the copy is within bounds of `dictionary` + `dest`.
 #[inline]
-unsafe fn decode_long_copy(
-    source: &mut *const u8,
-    dest: &mut *mut u8,
+fn copy_checked(
+    dictionary: &[u8],
+    dest: &mut [u8],
     file_size: &mut usize,
-) -> bool {
-    // Opcode 01, length 2 - 256
-    let ofs_bytes = read_two_le(source) as isize;
-    if ofs_bytes == 0 {
-        return true;
+    offset: isize,
+    length: usize,
+    input_offset: usize,
+) -> Result<(), PrsError> {
+    let distance = offset.unsigned_abs();
+    if distance > *file_size + dictionary.len() {
+        return Err(PrsError::InvalidBackReference { input_offset });
     }
 
-    // Obtain the offset. (negative i32, truncated to u16)
-    // We lost our negative sign when we originally wrote the offset, doing -0x2000 will restore it.
-    let offset = (ofs_bytes >> 3) | -0x2000;
+    let end = *file_size + length;
+    if end > dest.len() {
+        return Err(PrsError::OutputOverflow { input_offset });
+    }
 
-    // Perf:
-    // Calculate offset first, because length is more 'local', it's used by the
-    // loop, while ofs is only used once.
-    let length = ofs_bytes as usize & 0b111;
-    let length = if length == 0 {
-        read_byte(source) + 1 // length: 2 - 256
-    } else {
-        length + 2 // length: 2 - 9
-    };
+    // Copy the part (if any) which comes from before the start of `dest`.
+    let mut copied = 0;
+    if distance > *file_size {
+        let dict_start = dictionary.len() - (distance - *file_size);
+        copied = (distance - *file_size).min(length);
+        dest[*file_size..*file_size + copied]
+            .copy_from_slice(&dictionary[dict_start..dict_start + copied]);
+    }
 
-    let dest_local = *dest; // hoist the variable for perf
-    let src_addr = dest_local.wrapping_add(offset as usize);
-    for i in 0..length {
-        *dest_local.add(i) = *src_addr.add(i);
+    // Source and destination may overlap, so must copy byte by byte.
+    for i in copied..length {
+        dest[*file_size + i] = dest[*file_size + i - distance];
     }
 
-    *dest = dest_local.add(length);
-    *file_size += length;
-    false
+    *file_size = end;
+    Ok(())
 }
 
-#[inline]
-unsafe fn decode_short_copy(
-    control_byte: &mut usize,
-    current_bit_position: &mut usize,
-    source: &mut *const u8,
-    dest: &mut *mut u8,
-    file_size: &mut usize,
-) {
-    // Opcode 00, length 2-5
-    let mut length = retrieve_control_bit(control_byte, current_bit_position, source) << 1;
-    length |= retrieve_control_bit(control_byte, current_bit_position, source);
-    length += 2;
-
-    // Obtain the offset. (negative i32, truncated to byte)
-    // We lost our sign when we originally wrote the offset, doing -0x100 will restore it.
-    let offset = read_byte(source) as isize | -0x100; // negative
-
-    // Copy from source to dest
-    // LLVM is magical, it just optimises this knowing max length is 5.
-    // I have no idea how, given complexity of everything, but it does.
-    // This ends up being very nice unrolled code copy.
+/// Copies `length` bytes from `offset` bytes behind `dest`, and advances `dest` past them.
+#[inline(always)]
+unsafe fn copy_unchecked(dest: &mut *mut u8, file_size: &mut usize, offset: isize, length: usize) {
+    // Source and destination may overlap, so must copy byte by byte.
+    // LLVM unrolls this for short copies, knowing their max length is 5.
     let dest_local = *dest; // hoist the variable for perf
     let src_addr = dest_local.wrapping_add(offset as usize);
     for i in 0..length {
@@ -103,3 +205,83 @@ unsafe fn decode_short_copy(
     *dest = dest_local.add(length);
     *file_size += length;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_decompress_detects_back_reference_before_start() {
+        // 1 literal, then short copy (offset -2), which points before start of output.
+        let data = [0b0000_0001, 0x41, 0xFE];
+        let mut dest = [0_u8; 16];
+        assert_eq!(
+            prs_decompress_checked(&data, &mut dest),
+            Err(PrsError::InvalidBackReference { input_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn checked_decompress_detects_truncated_input() {
+        // 2 literals, then no terminator.
+        let data = [0b0000_0011, 0x41, 0x42];
+        let mut dest = [0_u8; 16];
+        assert_eq!(
+            prs_decompress_checked(&data, &mut dest),
+            Err(PrsError::UnexpectedEndOfInput { input_offset: 3 })
+        );
+    }
+
+    #[test]
+    fn checked_decompress_copies_from_dictionary() {
+        // Short copy (offset -2, length 4), then 1 literal, then terminator.
+        // Copy starts in the dictionary, and continues into the output.
+        let data = [0b0101_0100, 0xFE, 0x43, 0x00, 0x00];
+        let mut dest = [0_u8; 5];
+        assert_eq!(
+            prs_decompress_checked_with_dictionary(b"xAB", &data, &mut dest),
+            Ok(5)
+        );
+        assert_eq!(&dest, b"ABABC");
+
+        // Without the dictionary, the copy points before the start of output.
+        assert_eq!(
+            prs_decompress_checked(&data, &mut dest),
+            Err(PrsError::InvalidBackReference { input_offset: 1 })
+        );
+    }
+
+    #[test]
+    fn in_place_decompress_detects_overrun() {
+        // Literal, then short copy (offset -1, length 5), then terminator; decompresses to "AAAAAA".
+        let data = [0b0101_1001, 0x41, 0xFF, 0x00, 0x00];
+
+        // Output can catch up with the compressed data, but not overtake it.
+        let mut buf = [0_u8; 8];
+        buf[3..].copy_from_slice(&data);
+        assert_eq!(prs_decompress_in_place(&mut buf, 3), Ok(6));
+        assert_eq!(&buf[..6], b"AAAAAA");
+
+        let mut buf = [0_u8; 7];
+        buf[2..].copy_from_slice(&data);
+        assert_eq!(
+            prs_decompress_in_place(&mut buf, 2),
+            Err(PrsError::InPlaceOverrun { input_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn checked_decompress_detects_output_overflow() {
+        // 2 literals, then terminator.
+        let data = [0b0000_1011, 0x41, 0x42, 0x00, 0x00];
+        let mut dest = [0_u8; 1];
+        assert_eq!(
+            prs_decompress_checked(&data, &mut dest),
+            Err(PrsError::OutputOverflow { input_offset: 2 })
+        );
+
+        let mut dest = [0_u8; 2];
+        assert_eq!(prs_decompress_checked(&data, &mut dest), Ok(2));
+        assert_eq!(&dest, b"AB");
+    }
+}
@@ -1,70 +1,130 @@
-use super::common::{advance_byte, read_byte, read_two_le, retrieve_control_bit};
+use super::common::UncheckedSource;
+use super::tokens::{decode_opcode_unchecked, Token, Tokens};
+use crate::error::PrsError;
 
-pub(crate) unsafe fn prs_calculate_decompressed_size_impl(mut source: *const u8) -> usize {
-    let mut control_byte = read_byte(&mut source);
-    let mut current_bit_position = 0;
+pub(crate) unsafe fn prs_calculate_decompressed_size_impl(source: *const u8) -> usize {
+    let mut source = UncheckedSource::new(source);
     let mut file_size = 0;
 
     loop {
-        // Test for Direct Byte (Opcode 1)
-        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
-            source = source.add(1);
-            file_size += 1;
-            continue;
+        // Literal and copy offsets are never used, so aren't read.
+        match decode_opcode_unchecked(&mut source) {
+            Token::End => return file_size,
+            token => file_size += token.decompressed_len(),
         }
+    }
+}
 
-        // Opcode 1 failed, now testing for Opcode 0X
-        if retrieve_control_bit(&mut control_byte, &mut current_bit_position, &mut source) == 1 {
-            // Test for Opcode 01
-            // Append size of long copy, break if it's end of file.
-            if decode_long_copy(&mut source, &mut file_size) {
-                break;
-            }
-        } else {
-            // Do Opcode 00
-            decode_short_copy(
-                &mut control_byte,
-                &mut current_bit_position,
-                &mut source,
-                &mut file_size,
-            );
+/// Bounds checked counterpart of [`prs_calculate_decompressed_size_impl`].
+#[cfg(feature = "alloc")]
+pub(crate) fn prs_calculate_decompressed_size_checked(source: &[u8]) -> Result<usize, PrsError> {
+    let mut tokens = Tokens::new(source);
+    loop {
+        let info = tokens.read_token()?;
+        if info.token == Token::End {
+            return Ok(info.decompressed_offset);
+        }
+    }
+}
+
+/// Calculates how many bytes a buffer needs past the end of the decompressed data,
+/// so the stream in `source` can be decompressed in place from the end of the buffer.
+///
+/// This is the largest distance by which the output gets ahead of the compressed data read so far,
+/// if the compressed data ends where the decompressed data does.
+pub(crate) fn prs_calculate_in_place_margin_checked(source: &[u8]) -> Result<usize, PrsError> {
+    let mut tokens = Tokens::new(source);
+    let mut max_lead = 0;
+    loop {
+        let info = tokens.read_token()?;
+        let output_end = info.decompressed_offset + info.token.decompressed_len();
+        max_lead = max_lead.max(output_end.saturating_sub(tokens.position()));
+
+        if info.token == Token::End {
+            return Ok((max_lead + tokens.position()).saturating_sub(output_end));
         }
     }
+}
 
-    file_size
+/// Sizes of a single PRS stream, as returned by [`prs_scan_stream`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Length of the compressed stream, including the terminator.
+    pub compressed_len: usize,
+    /// Length of the data the stream decompresses to.
+    pub decompressed_len: usize,
 }
 
-#[inline]
-unsafe fn decode_long_copy(source: &mut *const u8, file_size: &mut usize) -> bool {
-    let offset = read_two_le(source);
-    if offset == 0 {
-        return true;
+/// Walks the PRS stream at the start of `source`, with bounds checking,
+/// and returns both its compressed and decompressed length.
+///
+/// Any data after the stream's terminator is ignored.
+pub fn prs_scan_stream(source: &[u8]) -> Result<StreamInfo, PrsError> {
+    let mut tokens = Tokens::new(source);
+    loop {
+        let info = tokens.read_token()?;
+        if info.token == Token::End {
+            return Ok(StreamInfo {
+                compressed_len: tokens.position(),
+                decompressed_len: info.decompressed_offset,
+            });
+        }
     }
+}
 
-    let length = offset & 0b111;
-    let length = if length == 0 {
-        read_byte(source) + 1
-    } else {
-        length + 2
-    };
+/// Iterator over PRS streams stored back to back, created by [`crate::decomp::prs_scan_streams`].
+///
+/// Yields the compressed data of each stream along with its [`StreamInfo`].
+/// Stops at the end of the input, or after the first error.
+pub struct PrsStreams<'a> {
+    /// Data which was not scanned yet.
+    remaining: &'a [u8],
+    /// Offset of `remaining` in the original input.
+    offset: usize,
+    /// Whether an error was returned.
+    failed: bool,
+}
 
-    *file_size += length;
-    false
+impl<'a> PrsStreams<'a> {
+    /// Creates an iterator over the concatenated PRS streams in `source`.
+    pub fn new(source: &'a [u8]) -> Self {
+        Self {
+            remaining: source,
+            offset: 0,
+            failed: false,
+        }
+    }
 }
 
-#[inline]
-unsafe fn decode_short_copy(
-    control_byte: &mut usize,
-    current_bit_position: &mut usize,
-    source: &mut *const u8,
-    file_size: &mut usize,
-) {
-    let mut length = retrieve_control_bit(control_byte, current_bit_position, source) << 1;
-    length |= retrieve_control_bit(control_byte, current_bit_position, source);
-    length += 2;
+impl<'a> Iterator for PrsStreams<'a> {
+    type Item = Result<(&'a [u8], StreamInfo), PrsError>;
 
-    // Simulate reading the offset
-    advance_byte(source);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.remaining.is_empty() {
+            return None;
+        }
 
-    *file_size += length;
+        match prs_scan_stream(self.remaining) {
+            Ok(info) => {
+                let (stream, remaining) = self.remaining.split_at(info.compressed_len);
+                self.remaining = remaining;
+                self.offset += info.compressed_len;
+                Some(Ok((stream, info)))
+            }
+            Err(err) => {
+                self.failed = true;
+                // Report the offset within the whole input, rather than within this stream.
+                Some(Err(match err {
+                    PrsError::UnexpectedEndOfInput { input_offset } => {
+                        PrsError::UnexpectedEndOfInput {
+                            input_offset: self.offset + input_offset,
+                        }
+                    }
+                    err => err,
+                }))
+            }
+        }
+    }
 }
+
+impl core::iter::FusedIterator for PrsStreams<'_> {}
@@ -0,0 +1,397 @@
+use super::decompress::{long_copy_length, long_copy_offset, short_copy_offset};
+use crate::error::PrsError;
+
+/// Size of the output ring buffer of [`PrsIncrementalDecoder`].
+/// Must be a power of 2, and larger than the max copy offset (0x1FFF).
+pub const RING_BUFFER_SIZE: usize = 0x2000;
+const RING_BUFFER_MASK: usize = RING_BUFFER_SIZE - 1;
+
+/// Status returned by [`PrsIncrementalDecoder::decode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeStatus {
+    /// All of the input was consumed, more compressed data is needed to continue.
+    NeedInput,
+    /// The output ring buffer is full, decompressed data must be pulled before continuing.
+    OutputFull,
+    /// The terminator of the PRS stream was reached. No more input will be consumed.
+    Finished,
+}
+
+/// Position within the PRS stream, i.e. what the decoder expects next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// First control bit of an opcode.
+    Opcode,
+    /// Second control bit of a `0X` opcode.
+    OpcodeZero,
+    /// The byte of a `1` opcode.
+    Literal,
+    /// First (high) length bit of a `00` opcode.
+    ShortCopyLengthHigh,
+    /// Second (low) length bit of a `00` opcode.
+    ShortCopyLengthLow { high: usize },
+    /// Offset byte of a `00` opcode.
+    ShortCopyOffset { length: usize },
+    /// First byte of the packed offset of a `01` opcode.
+    LongCopyOffsetLow,
+    /// Second byte of the packed offset of a `01` opcode.
+    LongCopyOffsetHigh { low: usize },
+    /// Extended length byte of a `01` opcode.
+    LongCopyLength { ofs_bytes: usize },
+    /// Outputting the bytes of a copy.
+    Copy { distance: usize, remaining: usize },
+    /// Reached the terminator.
+    Finished,
+}
+
+/// Resumable PRS decompressor, which does not need `std` or `alloc`.
+///
+/// Unlike [`crate::decomp::prs_decompress`], this does not need the whole compressed stream up front.
+/// Compressed data is pushed in via [`PrsIncrementalDecoder::decode`] as it arrives, in chunks of any size,
+/// and decompressed into an internal 8 KiB ring buffer, which the caller pulls from via
+/// [`PrsIncrementalDecoder::read_output`].
+///
+/// The ring buffer doubles as the back-reference window, so decompressed data can be pulled
+/// as soon as it is available.
+///
+/// # Example
+///
+/// ```
+/// use prs_rs::decomp::{DecodeStatus, PrsIncrementalDecoder};
+///
+/// // 'A', then copy 3 bytes from 1 byte back, then terminator.
+/// let compressed: &[u8] = &[0b0101_0001, 0x41, 0xFF, 0x00, 0x00];
+///
+/// let mut decoder = PrsIncrementalDecoder::new();
+/// let mut output = [0_u8; 16];
+/// let mut output_len = 0;
+/// for chunk in compressed.chunks(2) {
+///     let mut chunk = chunk;
+///     loop {
+///         let (consumed, status) = decoder.decode(chunk).unwrap();
+///         chunk = &chunk[consumed..];
+///         output_len += decoder.read_output(&mut output[output_len..]);
+///         if status != DecodeStatus::OutputFull {
+///             break;
+///         }
+///     }
+/// }
+///
+/// assert!(decoder.is_finished());
+/// assert_eq!(&output[..output_len], b"AAAA");
+/// ```
+///
+/// # Remarks
+///
+/// After an error is returned, the state of the decoder is unspecified, and it should no longer be used.
+pub struct PrsIncrementalDecoder {
+    /// Ring buffer holding the last [`RING_BUFFER_SIZE`] decompressed bytes.
+    ring_buffer: [u8; RING_BUFFER_SIZE],
+    /// Total number of decompressed bytes written to the ring buffer.
+    write_pos: usize,
+    /// Total number of decompressed bytes pulled from the ring buffer.
+    read_pos: usize,
+    /// Total number of compressed bytes consumed.
+    input_offset: usize,
+    /// Offset of the data bytes of the copy opcode being decoded, for error reporting.
+    copy_input_offset: usize,
+    control_byte: usize,
+    current_bit_position: usize,
+    state: State,
+}
+
+impl Default for PrsIncrementalDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrsIncrementalDecoder {
+    /// Creates a new decoder, at the start of a PRS stream.
+    pub const fn new() -> Self {
+        Self {
+            ring_buffer: [0; RING_BUFFER_SIZE],
+            write_pos: 0,
+            read_pos: 0,
+            input_offset: 0,
+            copy_input_offset: 0,
+            control_byte: 0,
+            current_bit_position: 8, // force read of first control byte
+            state: State::Opcode,
+        }
+    }
+
+    /// Returns `true` once the terminator of the PRS stream has been decoded.
+    ///
+    /// There may still be decompressed data left to pull at this point.
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Finished
+    }
+
+    /// Total number of compressed bytes consumed so far.
+    pub fn total_in(&self) -> usize {
+        self.input_offset
+    }
+
+    /// Total number of bytes decompressed so far, including bytes not yet pulled.
+    pub fn total_out(&self) -> usize {
+        self.write_pos
+    }
+
+    /// Decompresses as much of `input` as possible into the ring buffer.
+    ///
+    /// # Parameters
+    ///
+    /// - `input`: The next chunk of compressed data.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes consumed from `input`, and the reason decoding stopped.
+    /// Bytes which were not consumed must be passed again in the next call.
+    ///
+    /// Errors report the offset from the start of the PRS stream where the problem was encountered.
+    pub fn decode(&mut self, input: &[u8]) -> Result<(usize, DecodeStatus), PrsError> {
+        let mut pos = 0;
+
+        macro_rules! next_byte {
+            () => {
+                match input.get(pos) {
+                    Some(&byte) => {
+                        pos += 1;
+                        self.input_offset += 1;
+                        byte as usize
+                    }
+                    None => return Ok((pos, DecodeStatus::NeedInput)),
+                }
+            };
+        }
+
+        macro_rules! next_control_bit {
+            () => {{
+                if self.current_bit_position >= 8 {
+                    self.control_byte = next_byte!();
+                    self.current_bit_position = 0;
+                }
+
+                let return_value = self.control_byte & 0x01;
+                self.control_byte >>= 1;
+                self.current_bit_position += 1;
+                return_value
+            }};
+        }
+
+        loop {
+            self.state = match self.state {
+                State::Opcode => {
+                    if next_control_bit!() == 1 {
+                        State::Literal
+                    } else {
+                        State::OpcodeZero
+                    }
+                }
+                State::OpcodeZero => {
+                    if next_control_bit!() == 1 {
+                        self.copy_input_offset = self.input_offset;
+                        State::LongCopyOffsetLow
+                    } else {
+                        State::ShortCopyLengthHigh
+                    }
+                }
+                State::Literal => {
+                    if self.available_space() == 0 {
+                        return Ok((pos, DecodeStatus::OutputFull));
+                    }
+
+                    let byte = next_byte!();
+                    self.push(byte as u8);
+                    State::Opcode
+                }
+                State::ShortCopyLengthHigh => State::ShortCopyLengthLow {
+                    high: next_control_bit!(),
+                },
+                State::ShortCopyLengthLow { high } => State::ShortCopyOffset {
+                    length: ((high << 1) | next_control_bit!()) + 2,
+                },
+                State::ShortCopyOffset { length } => {
+                    self.copy_input_offset = self.input_offset;
+                    let offset = short_copy_offset(next_byte!());
+                    self.begin_copy(offset, length)?
+                }
+                State::LongCopyOffsetLow => State::LongCopyOffsetHigh { low: next_byte!() },
+                State::LongCopyOffsetHigh { low } => {
+                    let ofs_bytes = low | (next_byte!() << 8);
+                    if ofs_bytes == 0 {
+                        State::Finished
+                    } else {
+                        match long_copy_length(ofs_bytes) {
+                            Some(length) => self.begin_copy(long_copy_offset(ofs_bytes), length)?,
+                            None => State::LongCopyLength { ofs_bytes },
+                        }
+                    }
+                }
+                State::LongCopyLength { ofs_bytes } => {
+                    let length = next_byte!() + 1;
+                    self.begin_copy(long_copy_offset(ofs_bytes), length)?
+                }
+                State::Copy {
+                    distance,
+                    remaining,
+                } => {
+                    let num_bytes = remaining.min(self.available_space());
+                    for _ in 0..num_bytes {
+                        let byte = self.ring_buffer[(self.write_pos - distance) & RING_BUFFER_MASK];
+                        self.push(byte);
+                    }
+
+                    if num_bytes < remaining {
+                        self.state = State::Copy {
+                            distance,
+                            remaining: remaining - num_bytes,
+                        };
+                        return Ok((pos, DecodeStatus::OutputFull));
+                    }
+
+                    State::Opcode
+                }
+                State::Finished => return Ok((pos, DecodeStatus::Finished)),
+            };
+        }
+    }
+
+    /// Returns the decompressed data available to be pulled, without consuming it.
+    ///
+    /// As the data is stored in a ring buffer, this may not be all available data;
+    /// call [`PrsIncrementalDecoder::consume_output`] and then this again to get the rest.
+    pub fn output(&self) -> &[u8] {
+        let start = self.read_pos & RING_BUFFER_MASK;
+        let len = (self.write_pos - self.read_pos).min(RING_BUFFER_SIZE - start);
+        &self.ring_buffer[start..start + len]
+    }
+
+    /// Marks `num_bytes` bytes of decompressed data as pulled, freeing up space in the ring buffer.
+    ///
+    /// # Panics
+    ///
+    /// If `num_bytes` is greater than the amount of available data.
+    pub fn consume_output(&mut self, num_bytes: usize) {
+        assert!(num_bytes <= self.write_pos - self.read_pos);
+        self.read_pos += num_bytes;
+    }
+
+    /// Pulls as much decompressed data as possible into `out`.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written to `out`.
+    pub fn read_output(&mut self, out: &mut [u8]) -> usize {
+        let mut written = 0;
+        while written < out.len() {
+            let available = self.output();
+            if available.is_empty() {
+                break;
+            }
+
+            let num_bytes = available.len().min(out.len() - written);
+            out[written..written + num_bytes].copy_from_slice(&available[..num_bytes]);
+            self.read_pos += num_bytes;
+            written += num_bytes;
+        }
+
+        written
+    }
+
+    /// Number of bytes which can be decompressed before the ring buffer is full.
+    #[inline]
+    fn available_space(&self) -> usize {
+        RING_BUFFER_SIZE - (self.write_pos - self.read_pos)
+    }
+
+    /// Appends a decompressed byte to the ring buffer.
+    #[inline]
+    fn push(&mut self, byte: u8) {
+        self.ring_buffer[self.write_pos & RING_BUFFER_MASK] = byte;
+        self.write_pos += 1;
+    }
+
+    /// Returns the state for copying `length` bytes from `offset` (negative) bytes behind the current position.
+    #[inline]
+    fn begin_copy(&self, offset: isize, length: usize) -> Result<State, PrsError> {
+        let distance = offset.unsigned_abs();
+        if distance > self.write_pos {
+            return Err(PrsError::InvalidBackReference {
+                input_offset: self.copy_input_offset,
+            });
+        }
+
+        Ok(State::Copy {
+            distance,
+            remaining: length,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoder_detects_back_reference_before_start() {
+        // 1 literal, then short copy (offset -2), which points before start of output.
+        let data = [0b0000_0001, 0x41, 0xFE];
+        let mut decoder = PrsIncrementalDecoder::new();
+        assert_eq!(
+            decoder.decode(&data),
+            Err(PrsError::InvalidBackReference { input_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn decoder_resumes_mid_opcode() {
+        // 'A', long copy (offset -1, extended length 44), then terminator.
+        let data = [0b0001_0101, 0x41, 0xF8, 0xFF, 0x2B, 0x00, 0x00];
+        let mut decoder = PrsIncrementalDecoder::new();
+        for (x, byte) in data.iter().enumerate() {
+            let (consumed, status) = decoder.decode(core::slice::from_ref(byte)).unwrap();
+            assert_eq!(consumed, 1);
+            let expected = if x == data.len() - 1 {
+                DecodeStatus::Finished
+            } else {
+                DecodeStatus::NeedInput
+            };
+            assert_eq!(status, expected);
+        }
+
+        let mut out = [0_u8; 64];
+        assert_eq!(decoder.read_output(&mut out), 45);
+        assert!(out[..45].iter().all(|&x| x == 0x41));
+    }
+
+    #[test]
+    fn decoder_stops_when_output_full() {
+        let original: alloc::vec::Vec<u8> = (0..20000_u32).map(|x| (x % 251) as u8).collect();
+        let compressed = crate::comp::prs_compress(&original);
+
+        let mut decoder = PrsIncrementalDecoder::new();
+        let mut input = compressed.as_slice();
+        let mut decompressed = alloc::vec::Vec::new();
+        loop {
+            let (consumed, status) = decoder.decode(input).unwrap();
+            input = &input[consumed..];
+
+            if status == DecodeStatus::OutputFull {
+                let available = decoder.total_out() - decompressed.len();
+                assert_eq!(available, RING_BUFFER_SIZE);
+            }
+
+            let mut out = [0_u8; 1000];
+            let num_read = decoder.read_output(&mut out);
+            decompressed.extend_from_slice(&out[..num_read]);
+            if status == DecodeStatus::Finished && num_read == 0 {
+                break;
+            }
+        }
+
+        assert!(input.is_empty());
+        assert_eq!(original, decompressed);
+    }
+}
@@ -0,0 +1,350 @@
+use super::common::{
+    try_read_byte, try_read_two_le, try_retrieve_control_bit, OpcodeSource, UncheckedSource,
+};
+use super::decompress::{long_copy_length, long_copy_offset, short_copy_offset};
+use crate::error::PrsError;
+
+/// A single opcode of a PRS stream.
+///
+/// Copy offsets are negative, i.e. the number of bytes behind the current end of the decompressed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    /// Opcode `1`: a single byte, which is output as-is.
+    Literal(u8),
+    /// Opcode `00`: copy of 2 - 5 bytes, up to 0x100 bytes back.
+    ShortCopy { offset: isize, len: usize },
+    /// Opcode `01`: copy of up to 256 bytes, up to 0x2000 bytes back (0x1FFF if `extended`).
+    ///
+    /// If `extended`, the length (1 - 256) is stored in an extra byte after the offset,
+    /// otherwise it is packed (3 - 9) together with the offset.
+    LongCopy {
+        offset: isize,
+        len: usize,
+        extended: bool,
+    },
+    /// Opcode `01` with an offset of 0, which marks the end of the stream.
+    End,
+}
+
+impl Token {
+    /// Returns the number of bytes the opcode adds to the decompressed data.
+    pub fn decompressed_len(&self) -> usize {
+        match self {
+            Token::Literal(_) => 1,
+            Token::ShortCopy { len, .. } | Token::LongCopy { len, .. } => *len,
+            Token::End => 0,
+        }
+    }
+}
+
+/// Decodes the next opcode from `source`.
+///
+/// This is the only PRS opcode parser; the decompressors, the size estimators and [`Tokens`] all use it,
+/// with or without bounds checks depending on the [`OpcodeSource`]. It is always inlined, so reads
+/// whose result is unused (e.g. literals, when only calculating the decompressed size) are optimised out.
+#[inline(always)]
+pub(crate) fn decode_opcode<S: OpcodeSource>(source: &mut S) -> Result<Token, S::Error> {
+    // Opcode 1
+    if source.read_control_bit()? == 1 {
+        return Ok(Token::Literal(source.read_byte()? as u8));
+    }
+
+    // Opcode 01, offset 0 is end of file.
+    if source.read_control_bit()? == 1 {
+        let ofs_bytes = source.read_two_le()?;
+        if ofs_bytes == 0 {
+            return Ok(Token::End);
+        }
+
+        let offset = long_copy_offset(ofs_bytes);
+        return Ok(match long_copy_length(ofs_bytes) {
+            Some(len) => Token::LongCopy {
+                offset,
+                len,
+                extended: false,
+            },
+            None => Token::LongCopy {
+                offset,
+                len: source.read_byte()? + 1,
+                extended: true,
+            },
+        });
+    }
+
+    // Opcode 00
+    let mut len = source.read_control_bit()? << 1;
+    len |= source.read_control_bit()?;
+    len += 2;
+
+    let offset = short_copy_offset(source.read_byte()?);
+    Ok(Token::ShortCopy { offset, len })
+}
+
+/// Same as [`decode_opcode`], for a stream which is known to be valid.
+#[inline(always)]
+pub(crate) fn decode_opcode_unchecked(source: &mut UncheckedSource) -> Token {
+    match decode_opcode(source) {
+        Ok(token) => token,
+        Err(never) => match never {},
+    }
+}
+
+/// A [`Token`], along with where it was found in the compressed data, and where its output goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenInfo {
+    /// The decoded opcode.
+    pub token: Token,
+    /// Offset of the control byte containing the first bit of the opcode.
+    pub control_byte_offset: usize,
+    /// Index of the first bit of the opcode within its control byte (0 - 7, least significant bit first).
+    pub control_bit: u8,
+    /// Offset of the opcode's first data byte (the literal, or the copy's offset).
+    pub data_offset: usize,
+    /// Offset in the decompressed data where the opcode's output starts.
+    pub decompressed_offset: usize,
+}
+
+/// Iterator over the [`Token`]s of a PRS stream, created by [`crate::inspect::tokens`].
+///
+/// Yields every opcode up to and including [`Token::End`], and stops after it, or after the first error.
+///
+/// This is also the parser used by the bounds checked decompression and size estimation functions.
+pub struct Tokens<'a> {
+    source: &'a [u8],
+    reader: TokenReader,
+    /// Whether the end of the stream (or an error) was reached.
+    finished: bool,
+}
+
+impl<'a> Tokens<'a> {
+    /// Creates an iterator over the tokens of the PRS stream in `source`.
+    pub fn new(source: &'a [u8]) -> Self {
+        Self {
+            source,
+            reader: TokenReader::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the next opcode.
+    ///
+    /// Unlike [`Iterator::next`], this keeps reading past [`Token::End`] and errors.
+    pub(crate) fn read_token(&mut self) -> Result<TokenInfo, PrsError> {
+        self.reader.read_token(self.source)
+    }
+
+    /// Returns the number of compressed bytes read so far.
+    pub fn position(&self) -> usize {
+        self.reader.position()
+    }
+}
+
+/// The position of [`Tokens`] within a PRS stream, without the borrow of the stream itself.
+///
+/// Used where the compressed data is modified in between reading opcodes, i.e. in place decompression.
+/// The same `source` must be passed to every call of [`TokenReader::read_token`].
+pub(crate) struct TokenReader {
+    /// Offset of the next byte to read from the source.
+    pos: usize,
+    control_byte: usize,
+    current_bit_position: usize,
+    /// Offset of the control byte in `control_byte`.
+    control_byte_offset: usize,
+    /// Total length of the output of all opcodes read so far.
+    decompressed_offset: usize,
+}
+
+impl TokenReader {
+    pub(crate) fn new() -> Self {
+        Self {
+            pos: 0,
+            control_byte: 0,
+            current_bit_position: 8, // control byte is read on first use
+            control_byte_offset: 0,
+            decompressed_offset: 0,
+        }
+    }
+
+    /// Returns the number of compressed bytes read so far.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Reads the next opcode from `source`.
+    pub(crate) fn read_token(&mut self, source: &[u8]) -> Result<TokenInfo, PrsError> {
+        // The opcode starts at the next control bit, which may be in the next control byte.
+        let (control_byte_offset, control_bit) = if self.current_bit_position >= 8 {
+            (self.pos, 0)
+        } else {
+            (self.control_byte_offset, self.current_bit_position as u8)
+        };
+
+        let mut opcode_source = CheckedSource {
+            reader: self,
+            source,
+            data_offset: None,
+        };
+        let token = decode_opcode(&mut opcode_source)?;
+        let data_offset = opcode_source.data_offset.unwrap_or(self.pos);
+
+        let decompressed_offset = self.decompressed_offset;
+        self.decompressed_offset += token.decompressed_len();
+
+        Ok(TokenInfo {
+            token,
+            control_byte_offset,
+            control_bit,
+            data_offset,
+            decompressed_offset,
+        })
+    }
+
+    #[inline]
+    fn read_control_bit(&mut self, source: &[u8]) -> Result<usize, PrsError> {
+        if self.current_bit_position >= 8 {
+            self.control_byte_offset = self.pos;
+        }
+
+        try_retrieve_control_bit(
+            &mut self.control_byte,
+            &mut self.current_bit_position,
+            source,
+            &mut self.pos,
+        )
+    }
+}
+
+/// [`OpcodeSource`] reading from a slice, with bounds checks, and the position kept in a [`TokenReader`].
+struct CheckedSource<'a> {
+    reader: &'a mut TokenReader,
+    source: &'a [u8],
+    /// Offset of the opcode's first data byte, once read.
+    data_offset: Option<usize>,
+}
+
+impl CheckedSource<'_> {
+    #[inline(always)]
+    fn begin_data(&mut self) {
+        self.data_offset.get_or_insert(self.reader.pos);
+    }
+}
+
+impl OpcodeSource for CheckedSource<'_> {
+    type Error = PrsError;
+
+    #[inline(always)]
+    fn read_control_bit(&mut self) -> Result<usize, PrsError> {
+        self.reader.read_control_bit(self.source)
+    }
+
+    #[inline(always)]
+    fn read_byte(&mut self) -> Result<usize, PrsError> {
+        self.begin_data();
+        try_read_byte(self.source, &mut self.reader.pos)
+    }
+
+    #[inline(always)]
+    fn read_two_le(&mut self) -> Result<usize, PrsError> {
+        self.begin_data();
+        try_read_two_le(self.source, &mut self.reader.pos)
+    }
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Result<TokenInfo, PrsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.read_token();
+        self.finished = !matches!(result, Ok(TokenInfo { token, .. }) if token != Token::End);
+        Some(result)
+    }
+}
+
+impl core::iter::FusedIterator for Tokens<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn reads_all_opcodes_with_positions() {
+        // Literal 'A', literal 'B', short copy (len 2, offset -2), long copy (len 3, offset -4),
+        // then a second control byte, long copy (len 5, extended, offset -4), end.
+        let data: &[u8] = &[
+            0b1000_0011,
+            0x41,
+            0x42,
+            0xFE,
+            0xE1,
+            0xFF,
+            0b0000_1010,
+            0xE0,
+            0xFF,
+            0x04,
+            0x00,
+            0x00,
+        ];
+        let tokens: Vec<_> = Tokens::new(data).map(Result::unwrap).collect();
+        let summary: Vec<_> = tokens
+            .iter()
+            .map(|x| {
+                (
+                    x.token,
+                    x.control_byte_offset,
+                    x.control_bit,
+                    x.data_offset,
+                    x.decompressed_offset,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                (Token::Literal(b'A'), 0, 0, 1, 0),
+                (Token::Literal(b'B'), 0, 1, 2, 1),
+                (Token::ShortCopy { offset: -2, len: 2 }, 0, 2, 3, 2),
+                (
+                    Token::LongCopy {
+                        offset: -4,
+                        len: 3,
+                        extended: false
+                    },
+                    0,
+                    6,
+                    4,
+                    4
+                ),
+                (
+                    Token::LongCopy {
+                        offset: -4,
+                        len: 5,
+                        extended: true
+                    },
+                    6,
+                    0,
+                    7,
+                    7
+                ),
+                (Token::End, 6, 2, 10, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_after_error() {
+        // Literal, with the byte missing.
+        let data: &[u8] = &[0b0000_0001];
+        let mut tokens = Tokens::new(data);
+        assert_eq!(
+            tokens.next(),
+            Some(Err(PrsError::UnexpectedEndOfInput { input_offset: 1 }))
+        );
+        assert_eq!(tokens.next(), None);
+    }
+}
@@ -0,0 +1,33 @@
+pub use crate::impls::decomp::tokens::{Token, TokenInfo, Tokens};
+
+/// Returns an iterator over the opcodes of the PRS compressed data in `src`, for debugging.
+///
+/// # Parameters
+///
+/// - `src`: The compressed data.
+///
+/// # Returns
+///
+/// An iterator yielding each [`Token`] along with its position in the compressed and decompressed data,
+/// up to and including [`Token::End`]. Malformed data is reported as a [`crate::error::PrsError`],
+/// after which the iterator stops.
+///
+/// # Remarks
+///
+/// The tokens are not validated beyond what's needed to read them, e.g. a copy may reference data
+/// before the start of the output. Use [`crate::decomp::prs_decompress_to_vec`] to validate a stream.
+///
+/// # Example
+///
+/// ```
+/// use prs_rs::inspect::tokens;
+///
+/// let compressed = prs_rs::comp::prs_compress(b"abcabcabc");
+/// for info in tokens(&compressed) {
+///     let info = info.unwrap();
+///     println!("{:?} at {}", info.token, info.decompressed_offset);
+/// }
+/// ```
+pub fn tokens(src: &[u8]) -> Tokens<'_> {
+    Tokens::new(src)
+}
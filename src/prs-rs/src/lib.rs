@@ -1,6 +1,7 @@
 #![no_std]
 #![cfg_attr(feature = "nightly", feature(allocator_api))]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
@@ -9,6 +10,8 @@ pub(crate) mod prelude;
 
 pub mod comp;
 pub mod decomp;
+pub mod error;
+pub mod inspect;
 pub mod util;
 
 #[cfg(test)]
@@ -19,15 +22,32 @@ pub mod exports;
 
 pub mod impls {
     pub mod comp {
+        pub(crate) mod alloc_box;
         pub mod comp_dict;
         pub mod compress;
+        pub mod compressor;
+        #[cfg(feature = "std")]
+        pub mod encoder;
+        pub mod hash_chain;
         pub mod lz77_matcher;
+        pub(crate) mod match_length;
+        #[cfg(feature = "std")]
+        pub mod parallel;
+        pub mod scratch;
+        pub(crate) mod small_hash_chain;
+        pub mod stats;
+        #[cfg(feature = "alloc")]
+        pub mod writer;
     }
 
     pub mod decomp {
         pub(crate) mod common;
+        #[cfg(feature = "std")]
+        pub mod decoder;
         pub mod decompress;
         pub mod estimate;
+        pub mod incremental;
+        pub mod tokens;
     }
 }
 
@@ -11,20 +11,20 @@ pub use allocator_api2::alloc::Allocator;
 #[cfg(feature = "nightly")]
 pub use std::alloc::Allocator;
 
-#[cfg(not(feature = "nightly"))]
+#[cfg(all(feature = "alloc", not(feature = "nightly")))]
 pub use allocator_api2::alloc::Global;
 
 #[cfg(feature = "nightly")]
 pub use std::alloc::Global;
 
 #[cfg(not(feature = "nightly"))]
-pub use allocator_api2::boxed::Box;
+pub use allocator_api2::alloc::Layout;
 
 #[cfg(feature = "nightly")]
-pub use std::boxed::Box;
+pub use std::alloc::Layout;
 
 #[cfg(not(feature = "nightly"))]
-pub use allocator_api2::alloc::Layout;
+pub use allocator_api2::alloc::AllocError;
 
 #[cfg(feature = "nightly")]
-pub use std::alloc::Layout;
+pub use std::alloc::AllocError;
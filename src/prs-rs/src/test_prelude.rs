@@ -1,2 +1,116 @@
 //! Common imports for tests
 pub use crate::prelude::Global;
+
+use crate::prelude::{AllocError, Allocator, Layout};
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+/// Allocator which fails once the memory allocated from it at once would exceed a budget.
+/// Memory itself comes from [`Global`]; freed memory is returned to the budget.
+pub struct LimitedAllocator {
+    remaining: Cell<usize>,
+}
+
+impl LimitedAllocator {
+    /// Creates an allocator which allows up to `budget` bytes to be allocated at once.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            remaining: Cell::new(budget),
+        }
+    }
+
+    /// Returns the number of bytes which can still be allocated.
+    pub fn remaining(&self) -> usize {
+        self.remaining.get()
+    }
+}
+
+unsafe impl Allocator for LimitedAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let remaining = self
+            .remaining
+            .get()
+            .checked_sub(layout.size())
+            .ok_or(AllocError)?;
+        let result = Global.allocate(layout)?;
+        self.remaining.set(remaining);
+        Ok(result)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        Global.deallocate(ptr, layout);
+        self.remaining.set(self.remaining.get() + layout.size());
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use sparse_buffer::SparseBuffer;
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod sparse_buffer {
+    use core::ops::{Deref, DerefMut};
+    use core::ptr::null_mut;
+    use core::slice;
+
+    const PROT_READ: i32 = 0x1;
+    const PROT_WRITE: i32 = 0x2;
+    const MAP_PRIVATE: i32 = 0x02;
+    const MAP_ANONYMOUS: i32 = 0x20;
+    const MAP_NORESERVE: i32 = 0x4000;
+
+    extern "C" {
+        fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+        fn munmap(addr: *mut u8, len: usize) -> i32;
+    }
+
+    /// Zeroed memory mapped with `MAP_NORESERVE`, so only the pages which are touched use memory,
+    /// regardless of the OS's overcommit settings. Used to test inputs larger than 4 GiB.
+    pub struct SparseBuffer {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    impl SparseBuffer {
+        /// Maps `len` bytes of zeroed memory.
+        pub fn new(len: usize) -> Self {
+            let ptr = unsafe {
+                mmap(
+                    null_mut(),
+                    len,
+                    PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
+                    -1,
+                    0,
+                )
+            };
+            assert_ne!(ptr as isize, -1, "mmap failed");
+            Self { ptr, len }
+        }
+    }
+
+    impl Deref for SparseBuffer {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            unsafe { slice::from_raw_parts(self.ptr, self.len) }
+        }
+    }
+
+    impl DerefMut for SparseBuffer {
+        fn deref_mut(&mut self) -> &mut [u8] {
+            unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+    }
+
+    impl Drop for SparseBuffer {
+        fn drop(&mut self) {
+            unsafe { munmap(self.ptr, self.len) };
+        }
+    }
+}
@@ -1,9 +1,22 @@
 mod helpers;
-use helpers::samples::{get_uncompressed_file_path, load_sample_file};
-use prs_rs::comp::prs_compress_unsafe;
+use helpers::samples::{
+    get_compressed_file_path, get_uncompressed_file_path, incompressible_data, load_sample_file,
+};
+use more_asserts::{assert_le, assert_lt};
+use prs_rs::comp::{
+    prs_compress, prs_compress_into, prs_compress_parallel, prs_compress_scratch_size,
+    prs_compress_unsafe, prs_compress_with_dictionary, prs_compress_with_level,
+    prs_compress_with_options, prs_compress_with_scratch, prs_compress_with_stats,
+    CompressionLevel, CompressionOptions, PrsCompressor, PrsEncoder, PrsWriter,
+};
 use prs_rs::decomp::prs_decompress_unsafe;
+use prs_rs::decomp::{prs_decompress_to_vec, prs_decompress_with_dictionary};
+use prs_rs::error::PrsError;
+use prs_rs::inspect::{tokens, Token};
 use prs_rs::util::prs_calculate_max_compressed_size;
 use rstest::rstest;
+use std::io::Write;
+use std::mem::MaybeUninit;
 
 #[rstest]
 #[case::model("Model.bin")]
@@ -87,3 +100,616 @@ fn can_compress_and_decompress_file_with_nonzero_buffers(#[case] file_name: &str
         );
     }
 }
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn can_compress_and_decompress_file_safe(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let compressed = prs_compress(&original);
+    assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+
+    // Exact size buffer, goes through the intermediate buffer.
+    let mut comp_buf = vec![MaybeUninit::<u8>::uninit(); compressed.len()];
+    let compressed_size = prs_compress_into(&original, &mut comp_buf).unwrap();
+    assert_eq!(compressed.len(), compressed_size);
+
+    // Max size buffer, compresses directly.
+    let mut comp_buf =
+        vec![MaybeUninit::<u8>::uninit(); prs_calculate_max_compressed_size(original.len())];
+    let compressed_size = prs_compress_into(&original, &mut comp_buf).unwrap();
+    assert_eq!(compressed.len(), compressed_size);
+    let comp_buf: Vec<u8> = comp_buf[..compressed_size]
+        .iter()
+        .map(|x| unsafe { x.assume_init() })
+        .collect();
+    assert_eq!(compressed, comp_buf);
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::optimal(CompressionLevel::Optimal)]
+#[case::sega(CompressionLevel::Sega)]
+fn incompressible_data_fits_max_compressed_size(#[case] level: CompressionLevel) {
+    // With lengths of 8k+7, the literals and the terminator's control bits need an extra control byte.
+    for len in (7..400).step_by(8) {
+        let original = incompressible_data(len);
+        let max_len = prs_calculate_max_compressed_size(len);
+
+        let compressed = prs_compress_with_level(&original, level);
+        assert_le!(compressed.len(), max_len);
+        assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), original);
+
+        if level == CompressionLevel::Greedy {
+            let mut dest = vec![MaybeUninit::uninit(); max_len];
+            assert_eq!(
+                prs_compress_into(&original, &mut dest),
+                Ok(compressed.len())
+            );
+        }
+    }
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn compress_into_rejects_small_buffer(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let required = prs_compress(&original).len();
+
+    let mut comp_buf = vec![MaybeUninit::<u8>::uninit(); required - 1];
+    assert_eq!(
+        prs_compress_into(&original, &mut comp_buf),
+        Err(PrsError::BufferTooSmall { required })
+    );
+}
+
+#[rstest]
+#[case::model("Model.bin", 1)]
+#[case::layout("ObjectLayout.bin", 4096)]
+#[case::worstcase("WorstCase.bin", 100_000)]
+#[case::badending("BadEnding.bin", 7)]
+#[case::empty("Empty.bin", 1)]
+fn can_compress_file_streaming(#[case] file_name: &str, #[case] chunk_size: usize) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let mut encoder = PrsEncoder::new(Vec::new());
+    for chunk in original.chunks(chunk_size) {
+        encoder.write_all(chunk).unwrap();
+    }
+
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(prs_compress(&original), compressed);
+    assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn can_compress_and_decompress_file_optimal(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let compressed = prs_compress_with_level(&original, CompressionLevel::Optimal);
+    assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+    assert_le!(compressed.len(), prs_compress(&original).len());
+}
+
+#[test]
+fn optimal_compression_beats_greedy() {
+    // Greedy takes the 3 byte match for "abc" at offset -0x1000 as a long copy, then has to encode
+    // "d" + "efgh" separately. Optimal instead uses a short copy for "ab", then "cdefgh" as a single long copy.
+    let mut data = vec![0_u8; 0x1100];
+    data[0x10..0x16].copy_from_slice(b"cdefgh");
+    data[0x1000..0x1003].copy_from_slice(b"abc");
+    data[0x1020..0x1022].copy_from_slice(b"ab");
+    data.extend_from_slice(b"abcdefgh");
+
+    let greedy = prs_compress(&data);
+    let optimal = prs_compress_with_level(&data, CompressionLevel::Optimal);
+    assert_eq!(data, prs_decompress_to_vec(&optimal).unwrap());
+    assert_lt!(optimal.len(), greedy.len());
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn can_compress_and_decompress_file_lazy(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let options = CompressionOptions {
+        level: CompressionLevel::Lazy,
+        ..Default::default()
+    };
+    let compressed = prs_compress_with_options(&original, &options);
+    assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+}
+
+#[test]
+fn lazy_compression_beats_greedy() {
+    // Greedy takes the 3 byte match for "abc" at offset -0x1000, then has to encode "defghij" separately.
+    // Lazy sees the longer match for "bcdefghij" at the next byte, so encodes "a" as a literal instead.
+    let mut data = vec![0_u8; 0x1100];
+    data[0x10..0x19].copy_from_slice(b"bcdefghij");
+    data[0x1000..0x1003].copy_from_slice(b"abc");
+    data.extend_from_slice(b"abcdefghij");
+
+    let greedy = prs_compress(&data);
+    let lazy = prs_compress_with_level(&data, CompressionLevel::Lazy);
+    assert_eq!(data, prs_decompress_to_vec(&lazy).unwrap());
+    assert_lt!(lazy.len(), greedy.len());
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+fn sega_compression_matches_original_file(#[case] file_name: &str) {
+    // Recompressing the original game files must give identical data.
+    // (The other samples were not made by SEGA's compressor; one is modded, the other is all literals.)
+    let original = load_sample_file(get_compressed_file_path(file_name));
+    let decompressed = prs_decompress_to_vec(&original).unwrap();
+    let compressed = prs_compress_with_level(&decompressed, CompressionLevel::Sega);
+    assert_eq!(original, compressed);
+}
+
+#[rstest]
+#[case::model("Model.bin", 1)]
+#[case::layout("ObjectLayout.bin", 8)]
+#[case::worstcase("WorstCase.bin", 2)]
+#[case::badending("BadEnding.bin", 1)]
+#[case::empty("Empty.bin", 8)]
+fn can_compress_and_decompress_file_fast(#[case] file_name: &str, #[case] max_candidates: usize) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let options = CompressionOptions {
+        level: CompressionLevel::Fast,
+        max_candidates,
+    };
+    let compressed = prs_compress_with_options(&original, &options);
+    assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn fast_compression_with_unbounded_candidates_matches_greedy(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let options = CompressionOptions {
+        level: CompressionLevel::Fast,
+        max_candidates: usize::MAX,
+    };
+    assert_eq!(
+        prs_compress(&original),
+        prs_compress_with_options(&original, &options)
+    );
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::optimal(CompressionLevel::Optimal)]
+#[case::sega(CompressionLevel::Sega)]
+fn compressor_can_be_reused(#[case] level: CompressionLevel) {
+    let options = CompressionOptions::new(level);
+    let mut compressor = PrsCompressor::with_options(options);
+
+    // Mix of sizes, so buffers have to grow, and are reused with smaller inputs afterwards.
+    for file_name in [
+        "BadEnding.bin",
+        "Model.bin",
+        "Empty.bin",
+        "ObjectLayout.bin",
+        "BadEnding.bin",
+    ] {
+        let original = load_sample_file(get_uncompressed_file_path(file_name));
+        let compressed = compressor.compress_to_vec(&original);
+        assert_eq!(prs_compress_with_options(&original, &options), compressed);
+        assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+    }
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::optimal(CompressionLevel::Optimal)]
+#[case::sega(CompressionLevel::Sega)]
+fn compressor_output_fits_max_compressed_size(#[case] level: CompressionLevel) {
+    let mut compressor = PrsCompressor::with_options(CompressionOptions::new(level));
+
+    // Lengths of 8k+7 need the most control bytes; see `incompressible_data_fits_max_compressed_size`.
+    for len in (7..400).step_by(8) {
+        let original = incompressible_data(len);
+        let max_len = prs_calculate_max_compressed_size(len);
+
+        let compressed = compressor.compress_to_vec(&original);
+        assert_le!(compressed.len(), max_len);
+        assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), original);
+
+        // Compressed directly into `dest`, and via the compressor's own buffer.
+        for dest_len in [max_len, compressed.len()] {
+            let mut dest = vec![MaybeUninit::uninit(); dest_len];
+            assert_eq!(
+                compressor.compress(&original, &mut dest),
+                Ok(compressed.len())
+            );
+        }
+
+        let mut dest = vec![MaybeUninit::uninit(); max_len];
+        let compressed_len = compressor
+            .compress_with_dictionary(&original, &original, &mut dest)
+            .unwrap();
+        assert_le!(compressed_len, max_len);
+    }
+}
+
+#[test]
+fn compressor_reports_buffer_too_small() {
+    let original = load_sample_file(get_uncompressed_file_path("ObjectLayout.bin"));
+    let mut compressor = PrsCompressor::new();
+    let expected = compressor.compress_to_vec(&original);
+
+    let mut dest = vec![MaybeUninit::<u8>::uninit(); expected.len() - 1];
+    assert_eq!(
+        compressor.compress(&original, &mut dest),
+        Err(PrsError::BufferTooSmall {
+            required: expected.len()
+        })
+    );
+
+    let mut dest = vec![MaybeUninit::<u8>::uninit(); expected.len()];
+    assert_eq!(
+        compressor.compress(&original, &mut dest),
+        Ok(expected.len())
+    );
+    let dest: Vec<u8> = dest.iter().map(|x| unsafe { x.assume_init() }).collect();
+    assert_eq!(expected, dest);
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::optimal(CompressionLevel::Optimal)]
+fn can_compress_and_decompress_with_dictionary(#[case] level: CompressionLevel) {
+    // The start of the file is in the dictionary, so can be copied from it.
+    let original = load_sample_file(get_uncompressed_file_path("Model.bin"));
+    let dictionary = &original[..0x1FFF];
+
+    let mut compressor = PrsCompressor::with_options(CompressionOptions::new(level));
+    let mut dest = vec![MaybeUninit::<u8>::uninit(); original.len()];
+    let compressed_len = compressor
+        .compress_with_dictionary(dictionary, &original, &mut dest)
+        .unwrap();
+    let compressed: Vec<u8> = dest[..compressed_len]
+        .iter()
+        .map(|x| unsafe { x.assume_init() })
+        .collect();
+
+    assert_lt!(
+        compressed.len(),
+        compressor.compress_to_vec(&original).len()
+    );
+
+    let mut decompressed = vec![0_u8; original.len()];
+    assert_eq!(
+        prs_decompress_with_dictionary(dictionary, &compressed, &mut decompressed),
+        Ok(original.len())
+    );
+    assert_eq!(original, decompressed);
+
+    // Without the dictionary, the copies reference data before the start of the output.
+    assert!(matches!(
+        prs_decompress_to_vec(&compressed),
+        Err(PrsError::InvalidBackReference { .. })
+    ));
+}
+
+#[rstest]
+#[case::small("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+#[case::large("ObjectLayout.bin")]
+fn can_compress_and_decompress_with_large_dictionary(#[case] file_name: &str) {
+    // Only the end of the dictionary is used, so it can be passed as-is to both sides.
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let dictionary = load_sample_file(get_uncompressed_file_path("Model.bin"));
+
+    let mut dest =
+        vec![MaybeUninit::<u8>::uninit(); prs_calculate_max_compressed_size(original.len())];
+    let compressed_len = prs_compress_with_dictionary(&dictionary, &original, &mut dest).unwrap();
+    let compressed: Vec<u8> = dest[..compressed_len]
+        .iter()
+        .map(|x| unsafe { x.assume_init() })
+        .collect();
+
+    let mut decompressed = vec![0_u8; original.len()];
+    assert_eq!(
+        prs_decompress_with_dictionary(&dictionary, &compressed, &mut decompressed),
+        Ok(original.len())
+    );
+    assert_eq!(original, decompressed);
+}
+
+#[rstest]
+#[case::model("Model.bin", 4)]
+#[case::layout("ObjectLayout.bin", 2)]
+#[case::worstcase("WorstCase.bin", 3)]
+#[case::badending("BadEnding.bin", 8)]
+#[case::empty("Empty.bin", 4)]
+fn can_compress_and_decompress_file_parallel(#[case] file_name: &str, #[case] num_threads: usize) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    for level in [
+        CompressionLevel::Greedy,
+        CompressionLevel::Lazy,
+        CompressionLevel::Fast,
+    ] {
+        let compressed =
+            prs_compress_parallel(&original, &CompressionOptions::new(level), num_threads);
+        assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+    }
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn parallel_compression_with_one_thread_matches_serial(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let options = CompressionOptions::default();
+    assert_eq!(
+        prs_compress_with_options(&original, &options),
+        prs_compress_parallel(&original, &options, 1)
+    );
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+fn parallel_compression_fits_max_compressed_size(#[case] level: CompressionLevel) {
+    // 8k+7 bytes, split into 4 segments (segments are a multiple of 0xFFFF bytes).
+    let len = 8 * 24576 + 7;
+    let original = incompressible_data(len);
+
+    let compressed = prs_compress_parallel(&original, &CompressionOptions::new(level), 4);
+    assert_le!(compressed.len(), prs_calculate_max_compressed_size(len));
+    assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), original);
+}
+
+#[test]
+fn parallel_compression_handles_many_segments() {
+    // Large enough to be split into several segments.
+    let mut original = Vec::new();
+    for file_name in ["Model.bin", "ObjectLayout.bin", "BadEnding.bin"] {
+        original.extend(load_sample_file(get_uncompressed_file_path(file_name)));
+    }
+
+    let options = CompressionOptions::default();
+    let compressed = prs_compress_parallel(&original, &options, 16);
+    let mut decompressed = vec![0_u8; original.len()];
+    let decompressed_len =
+        unsafe { prs_decompress_unsafe(compressed.as_ptr(), decompressed.as_mut_ptr()) };
+    assert_eq!(decompressed_len, original.len());
+    assert_eq!(original, decompressed);
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+#[case::badending("BadEnding.bin")]
+#[case::empty("Empty.bin")]
+fn writer_reproduces_compressed_file(#[case] file_name: &str) {
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let compressed = prs_compress(&original);
+
+    let mut writer = PrsWriter::new();
+    for info in tokens(&compressed) {
+        match info.unwrap().token {
+            Token::End => break,
+            token => writer.write_token(token).unwrap(),
+        }
+    }
+
+    assert_eq!(writer.decompressed_len(), original.len());
+    assert_eq!(compressed, writer.finish());
+}
+
+#[rstest]
+#[case::offset_zero(Token::ShortCopy { offset: 0, len: 2 })]
+#[case::short_offset_too_far(Token::ShortCopy { offset: -0x101, len: 2 })]
+#[case::short_too_short(Token::ShortCopy { offset: -1, len: 1 })]
+#[case::short_too_long(Token::ShortCopy { offset: -1, len: 6 })]
+#[case::long_offset_too_far(Token::LongCopy { offset: -0x2001, len: 3, extended: false })]
+#[case::long_too_short(Token::LongCopy { offset: -1, len: 2, extended: false })]
+#[case::long_too_long(Token::LongCopy { offset: -1, len: 10, extended: false })]
+#[case::extended_too_short(Token::LongCopy { offset: -1, len: 0, extended: true })]
+#[case::extended_too_long(Token::LongCopy { offset: -1, len: 257, extended: true })]
+#[case::before_start(Token::LongCopy { offset: -0x1001, len: 3, extended: false })]
+#[case::end(Token::End)]
+fn writer_rejects_invalid_token(#[case] token: Token) {
+    let mut writer = PrsWriter::new();
+    for _ in 0..0x1000 {
+        writer.write_literal(0);
+    }
+
+    assert_eq!(
+        writer.write_token(token),
+        Err(PrsError::InvalidToken {
+            decompressed_offset: 0x1000
+        })
+    );
+
+    // Rejected tokens leave the stream untouched.
+    let compressed = writer.finish();
+    assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), vec![0; 0x1000]);
+}
+
+#[test]
+fn writer_accepts_max_long_copy_offset() {
+    let mut writer = PrsWriter::new();
+    let mut original: Vec<u8> = (0..0x2000).map(|x| x as u8).collect();
+    for &byte in &original {
+        writer.write_literal(byte);
+    }
+
+    // The decoder accepts a distance of 0x2000, as long as the length isn't extended,
+    // since an extended copy at that distance is the terminator.
+    let invalid = Err(PrsError::InvalidToken {
+        decompressed_offset: 0x2000,
+    });
+    assert_eq!(writer.write_long_copy(-0x2000, 3, true), invalid);
+    assert_eq!(writer.write_long_copy(-0x2001, 3, false), invalid);
+    writer.write_long_copy(-0x2000, 3, false).unwrap();
+    original.extend_from_slice(&[0, 1, 2]);
+
+    // Round trips through the inspector.
+    let compressed = writer.finish();
+    let last_copy = tokens(&compressed)
+        .map(|info| info.unwrap().token)
+        .filter(|&token| token != Token::End)
+        .last()
+        .unwrap();
+    assert_eq!(
+        last_copy,
+        Token::LongCopy {
+            offset: -0x2000,
+            len: 3,
+            extended: false
+        }
+    );
+
+    let mut rewriter = PrsWriter::new();
+    for info in tokens(&compressed) {
+        let token = info.unwrap().token;
+        if token != Token::End {
+            rewriter.write_token(token).unwrap();
+        }
+    }
+
+    assert_eq!(rewriter.finish(), compressed);
+    assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), original);
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::sega(CompressionLevel::Sega)]
+fn compression_stats_match_output(#[case] level: CompressionLevel) {
+    let original = load_sample_file(get_uncompressed_file_path("Model.bin"));
+    let (compressed, stats) = prs_compress_with_stats(&original, &CompressionOptions::new(level));
+    assert_eq!(compressed, prs_compress_with_level(&original, level));
+
+    // Every decompressed byte comes from exactly one opcode.
+    assert_eq!(stats.literals.bytes + stats.copies().bytes, original.len());
+    assert_eq!(stats.literals.count, stats.literals.bytes);
+    assert_eq!(
+        stats.length_histogram.iter().sum::<usize>(),
+        stats.copies().count
+    );
+    assert_eq!(
+        stats.offset_histogram.iter().sum::<usize>(),
+        stats.copies().count
+    );
+    assert!(stats.average_match_length() >= 2.0);
+
+    // Size in bits of all opcodes, plus terminator, rounded up to whole bytes.
+    let bits = stats.literals.count * 9
+        + stats.short_copies.count * 12
+        + stats.long_copies_small.count * 18
+        + stats.long_copies_large.count * 26
+        + 18;
+    assert_eq!(bits.div_ceil(8), compressed.len());
+
+    if level == CompressionLevel::Greedy {
+        assert!(stats.dict_init_time > std::time::Duration::ZERO);
+    } else {
+        assert_eq!(stats.dict_init_time, std::time::Duration::ZERO);
+    }
+}
+
+#[rstest]
+#[case::greedy(CompressionLevel::Greedy)]
+#[case::lazy(CompressionLevel::Lazy)]
+#[case::fast(CompressionLevel::Fast)]
+#[case::optimal(CompressionLevel::Optimal)]
+#[case::sega(CompressionLevel::Sega)]
+fn scratch_compression_matches_allocating_compression(#[case] level: CompressionLevel) {
+    let options = CompressionOptions::new(level);
+    let original = load_sample_file(get_uncompressed_file_path("ObjectLayout.bin"));
+    let expected = prs_compress_with_options(&original, &options);
+
+    // Misalign the scratch memory, to check the size accounts for alignment.
+    let scratch_size = prs_compress_scratch_size(original.len(), &options);
+    let mut scratch = vec![0xCC_u8; scratch_size + 1];
+    let mut dest = vec![MaybeUninit::uninit(); prs_calculate_max_compressed_size(original.len())];
+
+    // Scratch memory is reusable, regardless of its old contents.
+    for _ in 0..2 {
+        let compressed_len =
+            prs_compress_with_scratch(&original, &mut dest, &options, &mut scratch[1..]).unwrap();
+        let compressed: Vec<u8> = dest[..compressed_len]
+            .iter()
+            .map(|x| unsafe { x.assume_init() })
+            .collect();
+        assert_eq!(compressed, expected);
+    }
+}
+
+#[test]
+fn scratch_compression_rejects_small_buffers() {
+    let options = CompressionOptions::default();
+    let original = load_sample_file(get_uncompressed_file_path("ObjectLayout.bin"));
+    let scratch_size = prs_compress_scratch_size(original.len(), &options);
+    let max_len = prs_calculate_max_compressed_size(original.len());
+
+    let mut scratch = vec![0_u8; scratch_size - 1];
+    let mut dest = vec![MaybeUninit::uninit(); max_len];
+    assert_eq!(
+        prs_compress_with_scratch(&original, &mut dest, &options, &mut scratch),
+        Err(PrsError::ScratchTooSmall {
+            required: scratch_size
+        })
+    );
+
+    let mut scratch = vec![0_u8; scratch_size];
+    let mut dest = vec![MaybeUninit::uninit(); max_len - 1];
+    assert_eq!(
+        prs_compress_with_scratch(&original, &mut dest, &options, &mut scratch),
+        Err(PrsError::BufferTooSmall { required: max_len })
+    );
+}
+
+#[test]
+fn scratch_compression_fits_max_compressed_size() {
+    let options = CompressionOptions::default();
+    let mut scratch = vec![0_u8; prs_compress_scratch_size(400, &options)];
+
+    // Lengths of 8k+7 need the most control bytes; see `incompressible_data_fits_max_compressed_size`.
+    for len in (7..400).step_by(8) {
+        let original = incompressible_data(len);
+        let mut dest = vec![MaybeUninit::uninit(); prs_calculate_max_compressed_size(len)];
+        let compressed_len =
+            prs_compress_with_scratch(&original, &mut dest, &options, &mut scratch).unwrap();
+
+        let compressed: Vec<u8> = dest[..compressed_len]
+            .iter()
+            .map(|x| unsafe { x.assume_init() })
+            .collect();
+        assert_eq!(prs_decompress_to_vec(&compressed).unwrap(), original);
+    }
+}
@@ -1,7 +1,12 @@
 mod helpers;
 use helpers::samples::{get_compressed_file_path, get_uncompressed_file_path, load_sample_file};
-use prs_rs::decomp::prs_decompress_unsafe;
+use prs_rs::decomp::{
+    prs_calculate_in_place_margin, prs_decompress, prs_decompress_in_place, prs_decompress_to_vec,
+    prs_decompress_unsafe, DecodeStatus, PrsDecoder, PrsIncrementalDecoder,
+};
+use prs_rs::error::PrsError;
 use rstest::rstest;
+use std::io::Read;
 
 #[rstest]
 #[case::model("Model.bin")]
@@ -17,3 +22,154 @@ fn can_decompress_file(#[case] file_name: &str) {
     assert_eq!(expected.len(), decompressed_size);
     assert_eq!(expected.as_slice(), decomp_buf.as_slice());
 }
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn can_decompress_file_checked(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let mut decomp_buf = vec![0_u8; expected.len()];
+    let decompressed_size = prs_decompress(&compressed, &mut decomp_buf).unwrap();
+    assert_eq!(expected.len(), decompressed_size);
+    assert_eq!(expected.as_slice(), decomp_buf.as_slice());
+
+    let decompressed = prs_decompress_to_vec(&compressed).unwrap();
+    assert_eq!(expected, decompressed);
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn checked_decompress_rejects_truncated_file(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let truncated = &compressed[..compressed.len() / 2];
+
+    let mut decomp_buf = vec![0_u8; load_sample_file(get_uncompressed_file_path(file_name)).len()];
+    assert_eq!(
+        prs_decompress(truncated, &mut decomp_buf),
+        Err(PrsError::UnexpectedEndOfInput {
+            input_offset: truncated.len()
+        })
+    );
+    assert!(prs_decompress_to_vec(truncated).is_err());
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn checked_decompress_rejects_small_buffer(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let mut decomp_buf = vec![0_u8; expected.len() - 1];
+    assert!(matches!(
+        prs_decompress(&compressed, &mut decomp_buf),
+        Err(PrsError::OutputOverflow { .. })
+    ));
+}
+
+#[rstest]
+#[case::model("Model.bin", 1)]
+#[case::layout("ObjectLayout.bin", 7)]
+#[case::worstcase("WorstCase.bin", 4096)]
+fn can_decompress_file_streaming(#[case] file_name: &str, #[case] read_size: usize) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    // Append some trailing data, to ensure the decoder stops at the end of the stream.
+    let mut input = compressed.clone();
+    input.extend_from_slice(b"trailing");
+
+    let mut decoder = PrsDecoder::new(input.as_slice());
+    let mut decompressed = Vec::new();
+    let mut buf = vec![0_u8; read_size];
+    loop {
+        let num_read = decoder.read(&mut buf).unwrap();
+        if num_read == 0 {
+            break;
+        }
+        decompressed.extend_from_slice(&buf[..num_read]);
+    }
+
+    assert!(decoder.is_finished());
+    assert_eq!(expected, decompressed);
+    assert_eq!(decoder.into_inner(), b"trailing");
+}
+
+#[rstest]
+#[case::model("Model.bin", 1, 100)]
+#[case::layout("ObjectLayout.bin", 13, 8192)]
+#[case::worstcase("WorstCase.bin", 4096, 1)]
+fn can_decompress_file_incremental(
+    #[case] file_name: &str,
+    #[case] chunk_size: usize,
+    #[case] pull_size: usize,
+) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let mut decoder = PrsIncrementalDecoder::new();
+    let mut decompressed = Vec::new();
+    let mut buf = vec![0_u8; pull_size];
+    for chunk in compressed.chunks(chunk_size) {
+        let mut chunk = chunk;
+        loop {
+            let (consumed, status) = decoder.decode(chunk).unwrap();
+            chunk = &chunk[consumed..];
+
+            let num_read = decoder.read_output(&mut buf);
+            decompressed.extend_from_slice(&buf[..num_read]);
+            if status == DecodeStatus::NeedInput
+                || (status == DecodeStatus::Finished && num_read == 0)
+            {
+                break;
+            }
+        }
+    }
+
+    assert!(decoder.is_finished());
+    assert_eq!(compressed.len(), decoder.total_in());
+    assert_eq!(expected, decompressed);
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn can_decompress_file_in_place(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let margin = prs_calculate_in_place_margin(&compressed).unwrap();
+    let mut buf = vec![0_u8; expected.len() + margin];
+    let compressed_start = buf.len() - compressed.len();
+    buf[compressed_start..].copy_from_slice(&compressed);
+
+    let decompressed_size = prs_decompress_in_place(&mut buf, compressed_start).unwrap();
+    assert_eq!(expected.len(), decompressed_size);
+    assert_eq!(expected.as_slice(), &buf[..decompressed_size]);
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+fn in_place_decompress_rejects_insufficient_margin(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let margin = prs_calculate_in_place_margin(&compressed).unwrap();
+    assert!(margin > 0);
+
+    let mut buf = vec![0_u8; expected.len() + margin - 1];
+    let compressed_start = buf.len() - compressed.len();
+    buf[compressed_start..].copy_from_slice(&compressed);
+    assert!(matches!(
+        prs_decompress_in_place(&mut buf, compressed_start),
+        Err(PrsError::InPlaceOverrun { .. })
+    ));
+}
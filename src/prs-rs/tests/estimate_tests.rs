@@ -1,7 +1,10 @@
 mod helpers;
 use helpers::samples::{get_compressed_file_path, get_uncompressed_file_path, load_sample_file};
 use more_asserts::assert_le;
-use prs_rs::decomp::prs_calculate_decompressed_size;
+use prs_rs::decomp::{
+    prs_calculate_decompressed_size, prs_decompress_to_vec, prs_scan_stream, prs_scan_streams,
+};
+use prs_rs::error::PrsError;
 use prs_rs::util::prs_calculate_max_compressed_size;
 use rstest::rstest;
 
@@ -29,3 +32,72 @@ fn prs_calculate_max_compressed_size_is_sufficient(#[case] file_name: &str) {
         prs_calculate_max_compressed_size(uncompressed.len())
     );
 }
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn can_scan_file(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    let info = prs_scan_stream(&compressed).unwrap();
+    assert_eq!(info.compressed_len, compressed.len());
+    assert_eq!(info.decompressed_len, expected.len());
+
+    // Trailing data is not part of the stream.
+    let mut padded = compressed.clone();
+    padded.extend_from_slice(&[0xFF; 16]);
+    assert_eq!(prs_scan_stream(&padded), Ok(info));
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn scan_rejects_truncated_file(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let truncated = &compressed[..compressed.len() - 1];
+    assert_eq!(
+        prs_scan_stream(truncated),
+        Err(PrsError::UnexpectedEndOfInput {
+            input_offset: truncated.len()
+        })
+    );
+}
+
+#[test]
+fn can_scan_concatenated_streams() {
+    let files = ["Model.bin", "ObjectLayout.bin", "WorstCase.bin"];
+    let mut archive = Vec::new();
+    for file_name in files {
+        archive.extend(load_sample_file(get_compressed_file_path(file_name)));
+    }
+
+    let streams: Vec<_> = prs_scan_streams(&archive).map(Result::unwrap).collect();
+    assert_eq!(streams.len(), files.len());
+    for ((stream, info), file_name) in streams.into_iter().zip(files) {
+        let compressed = load_sample_file(get_compressed_file_path(file_name));
+        let expected = load_sample_file(get_uncompressed_file_path(file_name));
+        assert_eq!(stream, compressed.as_slice());
+        assert_eq!(info.compressed_len, compressed.len());
+        assert_eq!(prs_decompress_to_vec(stream).unwrap(), expected);
+    }
+}
+
+#[test]
+fn scan_streams_stops_after_truncated_stream() {
+    let model = load_sample_file(get_compressed_file_path("Model.bin"));
+    let mut archive = model.clone();
+    archive.extend_from_slice(&model[..model.len() / 2]);
+
+    let mut streams = prs_scan_streams(&archive);
+    assert!(streams.next().unwrap().is_ok());
+    assert_eq!(
+        streams.next().unwrap(),
+        Err(PrsError::UnexpectedEndOfInput {
+            input_offset: archive.len()
+        })
+    );
+    assert!(streams.next().is_none());
+}
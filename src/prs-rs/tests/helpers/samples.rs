@@ -25,3 +25,20 @@ pub fn load_sample_file(path: PathBuf) -> Vec<u8> {
     file.read_to_end(&mut buffer).expect("Unable to read file");
     buffer
 }
+
+/// Returns `len` bytes of data which can't be compressed at all.
+///
+/// This is a repeated de Bruijn sequence of every pair of bytes, so no 2 bytes repeat within 64KiB;
+/// further than any copy can reach.
+#[allow(dead_code)]
+pub fn incompressible_data(len: usize) -> Vec<u8> {
+    let mut sequence = Vec::with_capacity(256 * 256);
+    for first in 0..256_u16 {
+        sequence.push(first as u8);
+        for second in first + 1..256 {
+            sequence.extend([first as u8, second as u8]);
+        }
+    }
+
+    sequence.into_iter().cycle().take(len).collect()
+}
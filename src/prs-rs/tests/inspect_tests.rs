@@ -0,0 +1,98 @@
+mod helpers;
+use helpers::samples::{get_compressed_file_path, get_uncompressed_file_path, load_sample_file};
+use prs_rs::comp::{prs_compress_with_level, CompressionLevel};
+use prs_rs::decomp::{
+    prs_calculate_decompressed_size, prs_decompress, prs_decompress_to_vec, prs_decompress_unsafe,
+    prs_scan_stream,
+};
+use prs_rs::error::PrsError;
+use prs_rs::inspect::{tokens, Token};
+use rstest::rstest;
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn tokens_reproduce_file(#[case] file_name: &str) {
+    let compressed = load_sample_file(get_compressed_file_path(file_name));
+    let expected = load_sample_file(get_uncompressed_file_path(file_name));
+
+    // Replaying the tokens must give the original data back.
+    let mut decompressed = Vec::new();
+    let mut last_data_offset = 0;
+    for info in tokens(&compressed) {
+        let info = info.unwrap();
+        assert_eq!(info.decompressed_offset, decompressed.len());
+        assert!(info.data_offset > last_data_offset);
+        assert!(info.control_byte_offset < info.data_offset);
+        assert!(info.control_bit < 8);
+        last_data_offset = info.data_offset;
+
+        match info.token {
+            Token::Literal(byte) => decompressed.push(byte),
+            Token::ShortCopy { offset, len } | Token::LongCopy { offset, len, .. } => {
+                let start = decompressed.len() - offset.unsigned_abs();
+                for x in 0..len {
+                    decompressed.push(decompressed[start + x]);
+                }
+            }
+            Token::End => assert_eq!(info.data_offset + 2, compressed.len()),
+        }
+    }
+
+    assert_eq!(expected, decompressed);
+}
+
+#[rstest]
+#[case::model("Model.bin")]
+#[case::layout("ObjectLayout.bin")]
+#[case::worstcase("WorstCase.bin")]
+fn decoders_agree(#[case] file_name: &str) {
+    // The shipped files, plus the same data compressed at every level, so all opcode forms are covered.
+    let original = load_sample_file(get_uncompressed_file_path(file_name));
+    let mut streams = vec![load_sample_file(get_compressed_file_path(file_name))];
+    for level in [
+        CompressionLevel::Greedy,
+        CompressionLevel::Lazy,
+        CompressionLevel::Fast,
+        CompressionLevel::Optimal,
+    ] {
+        streams.push(prs_compress_with_level(&original, level));
+    }
+
+    for compressed in streams {
+        let token_len: usize = tokens(&compressed)
+            .map(|info| info.unwrap().token.decompressed_len())
+            .sum();
+        let estimated_len = unsafe { prs_calculate_decompressed_size(compressed.as_slice()) };
+        let scanned = prs_scan_stream(&compressed).unwrap();
+        assert_eq!(original.len(), token_len);
+        assert_eq!(original.len(), estimated_len);
+        assert_eq!(original.len(), scanned.decompressed_len);
+
+        let mut unchecked = vec![0u8; original.len()];
+        let unchecked_len =
+            unsafe { prs_decompress_unsafe(compressed.as_slice(), unchecked.as_mut_ptr()) };
+        let mut checked = vec![0u8; original.len()];
+        let checked_len = prs_decompress(&compressed, &mut checked).unwrap();
+        assert_eq!(original.len(), unchecked_len);
+        assert_eq!(original.len(), checked_len);
+        assert_eq!(original, unchecked);
+        assert_eq!(original, checked);
+        assert_eq!(original, prs_decompress_to_vec(&compressed).unwrap());
+    }
+}
+
+#[test]
+fn tokens_report_truncated_input() {
+    let compressed = load_sample_file(get_compressed_file_path("Model.bin"));
+    let truncated = &compressed[..compressed.len() / 2];
+
+    let last = tokens(truncated).last().unwrap();
+    assert_eq!(
+        last,
+        Err(PrsError::UnexpectedEndOfInput {
+            input_offset: truncated.len()
+        })
+    );
+}